@@ -1,3 +1,5 @@
+#define_import_path game_of_life::core
+
 // https://stackoverflow.com/questions/4200224/random-noise-functions-for-glsl
 fn randomFloat(xy: vec2<f32>, seed: f32) -> f32 {
     let offset = vec2<f32>(0.12345, 0.54321);
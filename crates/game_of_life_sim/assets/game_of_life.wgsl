@@ -1,13 +1,24 @@
-#import core.wgsl
+#import game_of_life::core::{randomFloat}
 
 @group(0) @binding(0)
 var image: texture_storage_2d<rgba16float, read_write>;
 @group(0) @binding(1)
 var data_in: texture_storage_2d<rgba16float, read_write>;
 
+// The result of the rule for every state and neighbor count, used by the storage table backend.
+// Look at `dsl/table.rs` for more info.
+struct RuleTable {
+    states: u32,
+    neighbors: u32,
+    entries: array<u32>,
+}
+
+@group(0) @binding(2)
+var<storage, read> rule_table: RuleTable;
+
 // =============================== INIT =============================== //
 
-@compute @workgroup_size(32, 32, 1)
+@compute @workgroup_size(#{WORKGROUP_SIZE}, #{WORKGROUP_SIZE}, 1)
 fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>, @builtin(num_workgroups) num_workgroups: vec3<u32>) {
     let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
 
@@ -20,33 +31,77 @@ fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>, @builtin(num_wo
 
 // =============================== COMPUTE =============================== //
 
-fn is_alive(location: vec2<i32>, offset_x: i32, offset_y: i32) -> u32 {
+// The number of states of the rule, 0 is dead, 1 is alive and the rest are dying
+const STATES: u32 = {STATES}u;
+
+// This will be replaced by the declarations the rule needs, like the inline table
+{DECLARATIONS}
+
+fn cell_state(location: vec2<i32>, offset_x: i32, offset_y: i32) -> u32 {
     let size = vec2<i32>(textureDimensions(data_in));
-    let loc = (location + vec2<i32>(offset_x, offset_y)) % vec2<i32>(size);
+#ifdef CLAMP_EDGES
+    // The grid ends at its edges, everything beyond them is dead
+    let loc = location + vec2<i32>(offset_x, offset_y);
+    if any(loc < vec2<i32>(0)) || any(loc >= size) {
+        return 0u;
+    }
+#else
+    // Add the size before wrapping, `%` keeps the sign of negative coordinates
+    let loc = (location + vec2<i32>(offset_x, offset_y) + size) % size;
+#endif
     let value: vec4<f32> = textureLoad(data_in, loc);
     return u32(value.x);
 }
 
-fn count_neighbors_simple(location: vec2<i32>) -> u32 {
-    var result: u32 = 0u;
-    for (var x: i32 = -1; x < 2; x++) {
-        for (var y: i32 = -1; y < 2; y++) {
-            if x == 0 && y == 0 {
-                continue;
-            }
-
-            result += is_alive(location, x, y); 
-        }
+// Only living cells count as neighbors, dying cells don't
+fn is_alive(location: vec2<i32>, offset_x: i32, offset_y: i32) -> u32 {
+    return u32(cell_state(location, offset_x, offset_y) == 1u);
+}
+
+// The state is kept in the red channel, green and blue fade out as a dying cell ages so every
+// state gets its own color
+fn state_color(state: u32) -> vec2<f32> {
+    if state < 2u {
+        return vec2<f32>(0.0, 0.0);
     }
-    
+    let age = f32(state - 1u) / f32(STATES - 1u);
+    return vec2<f32>(1.0 - age, age);
+}
+
+fn count_neighbors(location: vec2<i32>) -> u32 {
+    var result: u32 = 0u;
+
+    // This will be replaced by the neighborhood of the rule. Look at `dsl.rs` for more info.
+    {NEIGHBORHOOD}
+
     return result;
 }
 
-@compute @workgroup_size(32, 32, 1)
+#ifdef NEIGHBOR_MASK
+// Which of the 8 surrounding cells are alive, bit 0 is the top left cell and the bits go row by
+// row to the bottom right cell, skipping the cell itself. Used by isotropic non-totalistic rules,
+// `NEIGHBOR_MASK` is only defined for rules that read it.
+fn neighbor_mask(location: vec2<i32>) -> u32 {
+    return is_alive(location, -1, -1)
+        | (is_alive(location, 0, -1) << 1u)
+        | (is_alive(location, 1, -1) << 2u)
+        | (is_alive(location, -1, 0) << 3u)
+        | (is_alive(location, 1, 0) << 4u)
+        | (is_alive(location, -1, 1) << 5u)
+        | (is_alive(location, 0, 1) << 6u)
+        | (is_alive(location, 1, 1) << 7u);
+}
+#endif
+
+@compute @workgroup_size(#{WORKGROUP_SIZE}, #{WORKGROUP_SIZE}, 1)
 fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
-    let num_neighbors = count_neighbors_simple(location);
-    let is_alive = bool(is_alive(location, 0, 0));
+    let num_neighbors = count_neighbors(location);
+    let state = cell_state(location, 0, 0);
+    let is_alive = state == 1u;
+#ifdef NEIGHBOR_MASK
+    let neighbor_mask = neighbor_mask(location);
+#endif
 
     // This will be replaced by the DSL. Look at `dsl.rs` for more info.
     // It expects a result value of type `u32`.
@@ -54,6 +109,6 @@ fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     
     {PLACEHOLDER}
 
-    let color = vec4<f32>(f32(result), 0.0, 0.0, 1.0);
+    let color = vec4<f32>(f32(result), state_color(result), 1.0);
     textureStore(image, location, color);
 }
\ No newline at end of file
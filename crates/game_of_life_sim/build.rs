@@ -0,0 +1,49 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The extensions of the files baked in, shaders and the rule files of the rule library
+const EXTENSIONS: &[&str] = &[".wgsl", ".rule", ".ron", ".json"];
+
+/// Collect every shader and rule in `dir` and its subdirectories, skipping the dumps of generated
+/// shaders
+fn collect_assets(dir: &Path, assets: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            collect_assets(&path, assets);
+        } else if EXTENSIONS.iter().any(|extension| name.ends_with(extension))
+            && !name.ends_with(".debug.wgsl")
+        {
+            assets.push(path);
+        }
+    }
+}
+
+// Bake the shaders and rules in `assets` into the crate, for `EmbeddedAssets`
+fn main() {
+    let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+    println!("cargo:rerun-if-changed={}", assets.display());
+
+    let mut paths = Vec::new();
+    collect_assets(&assets, &mut paths);
+    paths.sort();
+
+    let mut embedded = String::from("pub const EMBEDDED_ASSETS: &[(&str, &str)] = &[\n");
+    for path in paths {
+        let relative = path.strip_prefix(&assets).unwrap().to_string_lossy();
+        embedded.push_str(&format!(
+            "    ({:?}, include_str!({:?})),\n",
+            relative.replace('\\', "/"),
+            path.display().to_string()
+        ));
+    }
+    embedded.push_str("];\n");
+
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("embedded_assets.rs"), embedded).unwrap();
+}
@@ -0,0 +1,171 @@
+//! Compose a shader the way the app does and write the result to stdout, without a GPU. Imports,
+//! defines and the code generated from a rule are filled in and the result is validated with
+//! naga, so errors point at the original files.
+
+use std::{io::Write, process::ExitCode};
+
+use game_of_life_sim::{dsl, shaders::ShaderImportProcessor, WORK_GROUP_SIZE};
+use naga::{
+    back::{glsl, spv},
+    valid::{Capabilities, ValidationFlags, Validator},
+};
+
+const USAGE: &str = "\
+Usage: compose_shader <SHADER> [OPTIONS]
+
+Compose SHADER from `assets`, validate it and write it to stdout as wgsl.
+
+Options:
+  -r, --rule <RULE>            Fill in the code generated from a rule, given as the name of a rule
+                               in the catalog or the library, a rulestring or a rule file
+  -b, --backend <BACKEND>      Generate the rule as `expression`, `inline-table` or
+                               `storage-table` (the default)
+  -D, --define <NAME[=VALUE]>  Define NAME for #ifdef and replace #{NAME} with VALUE, repeatable
+      --spirv                  Write SPIR-V instead of wgsl
+      --glsl <ENTRY_POINT>     Write GLSL 450 of a compute entry point instead of wgsl
+  -h, --help                   Print this message";
+
+enum Output {
+    Wgsl,
+    SpirV,
+    /// GLSL of the compute entry point with this name
+    Glsl(String),
+}
+
+struct Args {
+    shader: String,
+    rule: Option<String>,
+    backend: dsl::Backend,
+    defines: Vec<(String, String)>,
+    output: Output,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut shader = None;
+    let mut rule = None;
+    let mut backend = dsl::Backend::default();
+    let mut defines = Vec::new();
+    let mut output = Output::Wgsl;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} expects a value"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-r" | "--rule" => rule = Some(value()?),
+            "-b" | "--backend" => {
+                backend = match value()?.as_str() {
+                    "expression" => dsl::Backend::Expression,
+                    "inline-table" => dsl::Backend::InlineTable,
+                    "storage-table" => dsl::Backend::StorageTable,
+                    other => return Err(format!("unknown backend `{other}`")),
+                }
+            }
+            "-D" | "--define" => {
+                let define = value()?;
+                let (name, value) = define.split_once('=').unwrap_or((&define, ""));
+                defines.push((name.to_string(), value.to_string()));
+            }
+            "--spirv" => output = Output::SpirV,
+            "--glsl" => output = Output::Glsl(value()?),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+            _ if shader.is_none() => shader = Some(arg),
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+
+    let shader = shader.ok_or("expected the path of a shader in `assets`")?;
+    Ok(Some(Args {
+        shader,
+        rule,
+        backend,
+        defines,
+        output,
+    }))
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let mut processor = ShaderImportProcessor::with_asset_modules()
+        .map_err(|err| err.to_string())?
+        .with_define("WORKGROUP_SIZE", WORK_GROUP_SIZE);
+    for (name, value) in &args.defines {
+        processor = processor.with_define(name, value);
+    }
+
+    let shader = match &args.rule {
+        Some(rule) => {
+            let rule = dsl::find_rule(rule).map_err(|err| format!("{rule}: {err}"))?;
+            processor.load_source_with_dsl(&args.shader, &rule, args.backend)
+        }
+        None => processor.load_source(&args.shader),
+    }
+    .map_err(|err| err.to_string())?;
+
+    let mut stdout = std::io::stdout().lock();
+    let written = match args.output {
+        Output::Wgsl => stdout.write_all(shader.source.as_bytes()),
+        Output::SpirV => {
+            let (module, info) = validated_module(&shader.source)?;
+            let words = spv::write_vec(&module, &info, &spv::Options::default(), None)
+                .map_err(|err| format!("failed to write SPIR-V: {err}"))?;
+            stdout.write_all(bytemuck::cast_slice(&words))
+        }
+        Output::Glsl(entry_point) => {
+            let (module, info) = validated_module(&shader.source)?;
+            let options = glsl::Options {
+                version: glsl::Version::Desktop(450),
+                ..glsl::Options::default()
+            };
+            let pipeline_options = glsl::PipelineOptions {
+                shader_stage: naga::ShaderStage::Compute,
+                entry_point,
+                multiview: None,
+            };
+
+            let mut glsl = String::new();
+            glsl::Writer::new(
+                &mut glsl,
+                &module,
+                &info,
+                &options,
+                &pipeline_options,
+                naga::proc::BoundsCheckPolicies::default(),
+            )
+            .and_then(|mut writer| writer.write())
+            .map_err(|err| format!("failed to write GLSL: {err}"))?;
+            stdout.write_all(glsl.as_bytes())
+        }
+    };
+    written.map_err(|err| format!("failed to write to stdout: {err}"))
+}
+
+/// Parse and validate the composed wgsl again for the naga back-ends, `load_source` already
+/// reported any errors with their original locations
+fn validated_module(source: &str) -> Result<(naga::Module, naga::valid::ModuleInfo), String> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|err| err.emit_to_string(source))?;
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|err| err.emit_to_string(source))?;
+    Ok((module, info))
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
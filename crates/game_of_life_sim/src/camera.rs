@@ -31,6 +31,23 @@ impl OrthographicCamera {
         self.ortho.scale = self.ortho.scale.clamp(0.15, 5.);
     }
 
+    pub fn position(&self) -> Vec2 {
+        self.pos
+    }
+
+    pub fn set_position(&mut self, pos: Vec2) {
+        self.pos = pos;
+    }
+
+    /// How many world units a pixel of the screen covers, zooming in makes it smaller
+    pub fn scale(&self) -> f32 {
+        self.ortho.scale
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        self.zoom(scale / self.ortho.scale);
+    }
+
     /// Translates camera position
     pub fn translate(&mut self, translation: Vec2) {
         self.pos += translation;
@@ -5,11 +5,21 @@ use glass::{
     GlassContext,
 };
 
-use crate::SIM_SIZE;
+use crate::{
+    cpu::Grid,
+    dsl,
+    pattern::{Pattern, PatternError},
+    snapshot::SnapshotError,
+    SIM_SIZE,
+};
+
+/// Bytes of one `Rgba16Float` texel, the state of a cell is in the red channel
+const TEXEL_SIZE: u32 = 8;
 
 pub struct CanvasData {
     pub canvas: Texture,
     pub data_in: Texture,
+    pub rule_table: wgpu::Buffer,
 
     pub draw_bind_group: wgpu::BindGroup,
     pub init_bind_group: wgpu::BindGroup,
@@ -37,7 +47,10 @@ impl CanvasData {
                 mipmap_filter: wgpu::FilterMode::Nearest,
                 ..Default::default()
             },
-            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
         )
     }
 
@@ -50,6 +63,13 @@ impl CanvasData {
         let device = context.device();
         let canvas = Self::create_texture(context, SIM_SIZE, SIM_SIZE, "Canvas");
         let data_in = Self::create_texture(context, SIM_SIZE, SIM_SIZE, "Data In");
+        // Large enough for any rule so switching rules never needs a new buffer
+        let rule_table = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Rule Table"),
+            size: dsl::RuleTable::buffer_size(),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
 
         // Create bind groups to match pipeline layouts (except update, create that dynamically each frame)
         let canvas_bind_group =
@@ -69,6 +89,10 @@ impl CanvasData {
                     binding: 1,
                     resource: wgpu::BindingResource::TextureView(&data_in.views[0]),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: rule_table.as_entire_binding(),
+                },
             ],
         });
 
@@ -91,10 +115,188 @@ impl CanvasData {
         CanvasData {
             canvas,
             data_in,
+            rule_table,
 
             init_bind_group,
             draw_bind_group,
             canvas_bind_group,
         }
     }
+
+    /// Upload the table read by the storage table backend
+    pub fn write_rule_table(&self, context: &GlassContext, table: &dsl::RuleTable) {
+        context.queue().write_buffer(
+            &self.rule_table,
+            0,
+            bytemuck::cast_slice(&table.to_buffer()),
+        );
+    }
+
+    /// Place a pattern with the top left corner of its bounding box at `offset`, every cell in the
+    /// bounding box is replaced. Both textures get the cells, so they show right away and the
+    /// next update starts from them whichever texture it reads.
+    pub fn write_pattern(
+        &self,
+        context: &GlassContext,
+        pattern: &Pattern,
+        offset: (u32, u32),
+    ) -> Result<(), PatternError> {
+        let fits =
+            |start: u32, size: u32| start.checked_add(size).is_some_and(|end| end <= SIM_SIZE);
+        if !fits(offset.0, pattern.width) || !fits(offset.1, pattern.height) {
+            return Err(PatternError::TooLarge {
+                width: pattern.width.into(),
+                height: pattern.height.into(),
+                offset,
+                canvas: (SIM_SIZE, SIM_SIZE),
+            });
+        }
+        if pattern.width == 0 || pattern.height == 0 {
+            return Ok(());
+        }
+
+        let mut texels = vec![encode_state(0); (pattern.width * pattern.height) as usize];
+        for &(x, y) in &pattern.cells {
+            texels[(y * pattern.width + x) as usize] = encode_state(1);
+        }
+        self.write_texels(context, offset, (pattern.width, pattern.height), &texels);
+        Ok(())
+    }
+
+    /// Replace every cell of the canvas with the states of `grid`, which must be the size of the
+    /// canvas. Both textures get the cells like in `write_pattern`.
+    pub fn write_cells(&self, context: &GlassContext, grid: &Grid) -> Result<(), SnapshotError> {
+        if (grid.width(), grid.height()) != (SIM_SIZE, SIM_SIZE) {
+            return Err(SnapshotError::CanvasSize {
+                width: grid.width(),
+                height: grid.height(),
+            });
+        }
+        let texels = grid.cells().iter().map(|&state| encode_state(state));
+        self.write_texels(
+            context,
+            (0, 0),
+            (SIM_SIZE, SIM_SIZE),
+            &texels.collect::<Vec<_>>(),
+        );
+        Ok(())
+    }
+
+    /// Write a rectangle of texels at `offset` into both textures
+    fn write_texels(
+        &self,
+        context: &GlassContext,
+        offset: (u32, u32),
+        (width, height): (u32, u32),
+        texels: &[[u16; 4]],
+    ) {
+        for texture in [&self.data_in, &self.canvas] {
+            context.queue().write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: offset.0,
+                        y: offset.1,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(texels),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(width * TEXEL_SIZE),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    /// Copy the states of the cells in `texture` back to the CPU, this waits for the GPU to finish
+    /// the work submitted so far
+    pub fn read_cells(
+        context: &GlassContext,
+        texture: &Texture,
+    ) -> Result<Grid, wgpu::BufferAsyncError> {
+        let bytes = read_texture(context, &texture.texture, (SIM_SIZE, SIM_SIZE), TEXEL_SIZE)?;
+        let cells = bytes
+            .chunks_exact(TEXEL_SIZE as usize)
+            .map(|texel| decode_state(&bytemuck::pod_read_unaligned(texel)))
+            .collect();
+        Ok(Grid::from_cells(SIM_SIZE, SIM_SIZE, cells).unwrap())
+    }
+}
+
+/// Copy a texture of `size` with texels of `texel_size` bytes back to the CPU, row by row without
+/// the padding of the copy. This waits for the GPU to finish the work submitted so far.
+pub fn read_texture(
+    context: &GlassContext,
+    texture: &wgpu::Texture,
+    (width, height): (u32, u32),
+    texel_size: u32,
+) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+    let device = context.device();
+    // Rows of a copy to a buffer must be aligned
+    let row_size = width * texel_size;
+    let padded_row_size =
+        row_size.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded_row_size * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_size),
+                rows_per_image: Some(height),
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    context.queue().submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        sender.send(result).ok();
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+
+    let bytes = slice
+        .get_mapped_range()
+        .chunks_exact(padded_row_size as usize)
+        .flat_map(|row| row[..row_size as usize].to_vec())
+        .collect();
+    buffer.unmap();
+    Ok(bytes)
+}
+
+/// The texel of a cell in `state`, like the `init` kernel writes them
+fn encode_state(state: u32) -> [u16; 4] {
+    let channel = |value: f32| half::f16::from_f32(value).to_bits();
+    [channel(state as f32), 0, 0, channel(1.0)]
+}
+
+/// The state of the cell of a texel, read like `cell_state` in the update shader
+fn decode_state(texel: &[u16; 4]) -> u32 {
+    half::f16::from_bits(texel[0]).to_f32() as u32
 }
@@ -0,0 +1,226 @@
+use crate::{
+    dsl::{NeighborOffset, Neighborhood, Rule},
+    Boundary,
+};
+
+/// A CPU copy of the simulation grid, stepped with the same rules as the `update` kernel in
+/// `game_of_life.wgsl`. Edges behave like they do on the GPU with the same `Boundary`, which makes
+/// this usable as a reference to compare GPU output against cell for cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    width: u32,
+    height: u32,
+    cells: Vec<u32>,
+    boundary: Boundary,
+}
+
+impl Grid {
+    /// Creates an empty grid where every cell is dead
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![0; (width * height) as usize],
+            boundary: Boundary::default(),
+        }
+    }
+
+    /// Creates a grid from row-major cell values, returns `None` if the number of cells doesn't
+    /// match the size
+    pub fn from_cells(width: u32, height: u32, cells: Vec<u32>) -> Option<Self> {
+        if width.checked_mul(height) != u32::try_from(cells.len()).ok() {
+            return None;
+        }
+
+        Some(Self {
+            width,
+            height,
+            cells,
+            boundary: Boundary::default(),
+        })
+    }
+
+    /// Sets what the cells at the edges see beyond them, the grid wraps around by default
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Row-major cell values
+    pub fn cells(&self) -> &[u32] {
+        &self.cells
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> u32 {
+        self.cells[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, value: u32) {
+        let index = self.index(x, y);
+        self.cells[index] = value;
+    }
+
+    /// Whether a cell is in the living state. In multi-state rules dying cells are not alive and
+    /// don't count as neighbors.
+    pub fn is_alive(&self, x: u32, y: u32) -> bool {
+        self.get(x, y) == 1
+    }
+
+    /// Counts the living cells in `neighborhood` around a cell
+    pub fn count_neighbors(&self, x: u32, y: u32, neighborhood: &Neighborhood) -> u32 {
+        self.count_offsets(x, y, &neighborhood.offsets())
+    }
+
+    /// Which of the 8 surrounding cells are alive, in the bit order of `Expr::NeighborMask`
+    pub fn neighbor_mask(&self, x: u32, y: u32) -> u32 {
+        let offsets = (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter(|offset| *offset != (0, 0));
+        offsets.enumerate().fold(0, |mask, (bit, (dx, dy))| {
+            mask | (self.is_neighbor_alive(x as i64 + dx, y as i64 + dy) as u32) << bit
+        })
+    }
+
+    /// Advances the whole grid by one generation using `rule`, which must pass `Rule::check`
+    pub fn step(&self, rule: &Rule) -> Self {
+        let offsets = rule.neighborhood.offsets();
+        let mut next = Self::new(self.width, self.height).with_boundary(self.boundary);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let neighbors = self.count_offsets(x, y, &offsets);
+                let mask = self.neighbor_mask(x, y);
+                next.set(
+                    x,
+                    y,
+                    rule.statement.eval_cell(self.get(x, y), neighbors, mask),
+                );
+            }
+        }
+
+        next
+    }
+
+    fn count_offsets(&self, x: u32, y: u32, offsets: &[NeighborOffset]) -> u32 {
+        offsets
+            .iter()
+            .filter(|offset| {
+                self.is_neighbor_alive(x as i64 + offset.x as i64, y as i64 + offset.y as i64)
+            })
+            .map(|offset| offset.weight)
+            .sum()
+    }
+
+    /// Whether the cell at a position that may lie beyond the edges is alive
+    fn is_neighbor_alive(&self, x: i64, y: i64) -> bool {
+        let (width, height) = (self.width as i64, self.height as i64);
+        match self.boundary {
+            Boundary::Wrap => {
+                self.is_alive(x.rem_euclid(width) as u32, y.rem_euclid(height) as u32)
+            }
+            Boundary::Clamp => {
+                (0..width).contains(&x)
+                    && (0..height).contains(&y)
+                    && self.is_alive(x as u32, y as u32)
+            }
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::rulesets;
+
+    const BOUNDARIES: [Boundary; 2] = [Boundary::Wrap, Boundary::Clamp];
+
+    fn grid(size: u32, alive: &[(u32, u32)], boundary: Boundary) -> Grid {
+        let mut grid = Grid::new(size, size).with_boundary(boundary);
+        for &(x, y) in alive {
+            grid.set(x, y, 1);
+        }
+        grid
+    }
+
+    fn life() -> Rule {
+        rulesets::by_name("conway").unwrap()
+    }
+
+    #[test]
+    fn block_is_still() {
+        for boundary in BOUNDARIES {
+            let block = grid(6, &[(2, 2), (3, 2), (2, 3), (3, 3)], boundary);
+            assert_eq!(block.step(&life()), block, "{boundary:?}");
+        }
+    }
+
+    #[test]
+    fn blinker_oscillates() {
+        for boundary in BOUNDARIES {
+            let vertical = grid(5, &[(2, 1), (2, 2), (2, 3)], boundary);
+            let horizontal = grid(5, &[(1, 2), (2, 2), (3, 2)], boundary);
+            assert_eq!(vertical.step(&life()), horizontal, "{boundary:?}");
+            assert_eq!(horizontal.step(&life()), vertical, "{boundary:?}");
+        }
+    }
+
+    #[test]
+    fn blinker_at_the_edge() {
+        let cells = [(0, 1), (0, 2), (0, 3)];
+        let wrapped = grid(5, &cells, Boundary::Wrap).step(&life());
+        assert_eq!(wrapped, grid(5, &[(4, 2), (0, 2), (1, 2)], Boundary::Wrap));
+
+        // The cell that would be born beyond the edge is missing, so the blinker dies out
+        let clamped = grid(5, &cells, Boundary::Clamp).step(&life());
+        assert_eq!(clamped, grid(5, &[(0, 2), (1, 2)], Boundary::Clamp));
+        assert_eq!(clamped.step(&life()), grid(5, &[], Boundary::Clamp));
+    }
+
+    #[test]
+    fn glider_moves_diagonally() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        for boundary in BOUNDARIES {
+            let start = grid(8, &glider, boundary);
+            let moved = glider.map(|(x, y)| (x + 1, y + 1));
+            let next = (0..4).fold(start.clone(), |grid, _| grid.step(&life()));
+            assert_eq!(next, grid(8, &moved, boundary), "{boundary:?}");
+        }
+
+        // Around a torus the glider comes back to where it started
+        let start = grid(8, &glider, Boundary::Wrap);
+        let next = (0..32).fold(start.clone(), |grid, _| grid.step(&life()));
+        assert_eq!(next, start);
+
+        // Against the edges it turns into a block in the corner
+        let start = grid(8, &glider, Boundary::Clamp);
+        let next = (0..32).fold(start, |grid, _| grid.step(&life()));
+        assert_eq!(
+            next,
+            grid(8, &[(6, 6), (7, 6), (6, 7), (7, 7)], Boundary::Clamp)
+        );
+    }
+
+    #[test]
+    fn neighbor_mask_bits() {
+        for boundary in BOUNDARIES {
+            let grid = grid(3, &[(0, 0), (2, 1), (1, 2)], boundary);
+            assert_eq!(grid.neighbor_mask(1, 1), 1 | 1 << 4 | 1 << 6);
+        }
+        // Beyond the top left corner wraps to the bottom right one
+        let corner = grid(4, &[(3, 3)], Boundary::Wrap);
+        assert_eq!(corner.neighbor_mask(0, 0), 1);
+        let corner = grid(4, &[(3, 3)], Boundary::Clamp);
+        assert_eq!(corner.neighbor_mask(0, 0), 0);
+    }
+}
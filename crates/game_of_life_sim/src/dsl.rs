@@ -1,15 +1,63 @@
 #![allow(dead_code)]
 
+use serde::{Deserialize, Serialize};
+
+mod check;
+mod eval;
+pub mod hensel;
+mod library;
+mod neighborhood;
+mod optimize;
+mod parser;
+mod printer;
+mod rulestring;
+mod table;
+
+pub use check::{CheckError, Type, MAX_STATES};
+pub use hensel::IsotropicRule;
+pub use library::{
+    find_rule, library_rules, load_rule, load_rule_from, read_rule, write_rule, LoadError,
+    RULES_DIR, RULE_EXTENSIONS,
+};
+pub use neighborhood::{NeighborOffset, Neighborhood};
+pub use optimize::Bounds;
+pub use parser::{parse, parse_expr, parse_rule, ParseError};
+pub use rulestring::{parse_rulestring, GenerationsRule, LargerThanLifeRule, LifeLikeRule};
+pub use table::{Backend, RuleTable, MAX_TABLE_ENTRIES};
+
 /**
  * An expression in the domain specific language we use to describe cellular automata. Expressions
  * can perform arbitrary arithmetic and comparisons between constants, a boolean that indicates
  * whether the cell is currently alive, and the number of neighbors that a cell currently has.
+ * Multi-state rules can also read the full `State` of the cell, where 0 is dead, 1 is alive and
+ * higher states are dying. Intermediate values can be given a name with `Let` and referred to
+ * with `Var`.
  */
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Expr {
     U32(u32),
     Alive,
+    State,
     Neighbors,
+    /// Which of the eight cells around the cell are alive, one bit per cell in row-major order:
+    /// bit 0 is the top left cell and bit 7 the bottom right one. See `hensel`.
+    NeighborMask,
+    Var(String),
+    Let {
+        name: String,
+        value: Box<Expr>,
+        body: Box<Expr>,
+    },
+    Not(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Mod(Box<Expr>, Box<Expr>),
+    Min(Box<Expr>, Box<Expr>),
+    Max(Box<Expr>, Box<Expr>),
+    /// Shift right, only the lowest 5 bits of the shift amount are used like in wgsl
+    Shr(Box<Expr>, Box<Expr>),
     Gt(Box<Expr>, Box<Expr>),
     Gte(Box<Expr>, Box<Expr>),
     Lt(Box<Expr>, Box<Expr>),
@@ -23,42 +71,92 @@ impl Expr {
     /**
      * This method converts an Expr to an equivalent wgsl code fragment. This is not a valid wgsl
      * program, just an expression in wgsl. When used by statements it can form a complete wgsl
-     * program.
+     * program. The fragment has the natural type of the expression, see `check::Type`.
      */
     pub fn to_shader(&self) -> String {
+        self.to_shader_in(&mut Vec::new()).0
+    }
+
+    /**
+     * Convert an Expr to a wgsl code fragment of the given type, inserting a conversion if the
+     * expression has the other type.
+     */
+    pub fn to_shader_as(&self, ty: Type) -> String {
+        let (code, from) = self.to_shader_in(&mut Vec::new());
+        Type::convert(code, from, ty)
+    }
+
+    /**
+     * wgsl has no expression level bindings, so `Let` is inlined: every `Var` is replaced by the
+     * code of the value it is bound to in `scope`. Expressions have no side effects, so this is
+     * equivalent to evaluating the value once.
+     */
+    fn to_shader_in(&self, scope: &mut Vec<(String, String, Type)>) -> (String, Type) {
         use Expr::*;
 
         match self {
-            U32(val) => format!("{}u", val),
-            Alive => "is_alive".to_string(),
-            Neighbors => "num_neighbors".to_string(),
-            Gt(lhs, rhs) => format!(
-                "u32(({}) > ({}))",
-                Self::to_shader(lhs),
-                Self::to_shader(rhs)
-            ),
-            Gte(lhs, rhs) => format!(
-                "u32(({}) >= ({}))",
-                Self::to_shader(lhs),
-                Self::to_shader(rhs)
-            ),
-            Lt(lhs, rhs) => format!(
-                "u32(({}) < ({}))",
-                Self::to_shader(lhs),
-                Self::to_shader(rhs)
-            ),
-            Lte(lhs, rhs) => format!(
-                "u32(({}) <= ({}))",
-                Self::to_shader(lhs),
-                Self::to_shader(rhs)
-            ),
-            And(lhs, rhs) => format!("(({}) & ({}))", Self::to_shader(lhs), Self::to_shader(rhs)),
-            Or(lhs, rhs) => format!("(({}) | ({}))", Self::to_shader(lhs), Self::to_shader(rhs)),
-            Equal(lhs, rhs) => format!(
-                "u32(({}) == ({}))",
-                Self::to_shader(lhs),
-                Self::to_shader(rhs)
-            ),
+            Var(name) => match scope.iter().rev().find(|(bound, ..)| bound == name) {
+                Some((_, code, ty)) => (format!("({})", code), *ty),
+                None => (name.clone(), Type::U32),
+            },
+            Let { name, value, body } => {
+                let (value, ty) = value.to_shader_in(scope);
+                scope.push((name.clone(), value, ty));
+                let body = body.to_shader_in(scope);
+                scope.pop();
+                body
+            }
+            _ => {
+                let operands = self
+                    .operands()
+                    .iter()
+                    .map(|operand| operand.to_shader_in(scope))
+                    .collect::<Vec<_>>();
+                let types = operands.iter().map(|(_, ty)| *ty).collect::<Vec<_>>();
+                let ty = Type::of(self, &types);
+
+                // Booleans can be compared with each other, everything else compares integers
+                let operand_type = match self {
+                    Not(_) => Type::Bool,
+                    And(..) | Or(..) | Equal(..) if types.iter().all(|ty| *ty == Type::Bool) => {
+                        Type::Bool
+                    }
+                    _ => Type::U32,
+                };
+                let operands = operands
+                    .into_iter()
+                    .map(|(code, from)| Type::convert(code, from, operand_type))
+                    .collect::<Vec<_>>();
+                let binary = |op: &str| format!("(({}) {} ({}))", operands[0], op, operands[1]);
+
+                let code = match self {
+                    U32(val) => format!("{}u", val),
+                    Alive => "is_alive".to_string(),
+                    State => "state".to_string(),
+                    Neighbors => "num_neighbors".to_string(),
+                    NeighborMask => "neighbor_mask".to_string(),
+                    Not(_) => format!("!({})", operands[0]),
+                    Add(..) => binary("+"),
+                    Sub(..) => binary("-"),
+                    Mul(..) => binary("*"),
+                    Mod(..) => binary("%"),
+                    Min(..) => format!("min(({}), ({}))", operands[0], operands[1]),
+                    Max(..) => format!("max(({}), ({}))", operands[0], operands[1]),
+                    Shr(..) => binary(">>"),
+                    Gt(..) => binary(">"),
+                    Gte(..) => binary(">="),
+                    Lt(..) => binary("<"),
+                    Lte(..) => binary("<="),
+                    And(..) if ty == Type::Bool => binary("&&"),
+                    Or(..) if ty == Type::Bool => binary("||"),
+                    And(..) => binary("&"),
+                    Or(..) => binary("|"),
+                    Equal(..) => binary("=="),
+                    Var(_) | Let { .. } => unreachable!("bindings are handled above"),
+                };
+
+                (code, ty)
+            }
         }
     }
 }
@@ -68,7 +166,8 @@ impl Expr {
  * conditionally branch on expressions or set whether the current cell is alive or dead to the
  * result of an expression. Through statements we can describe complex rules to form cellular automata.
  */
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Statement {
     Void,
     SetResult(Expr),
@@ -89,14 +188,14 @@ impl Statement {
 
         match self {
             Void => String::new(),
-            SetResult(expr) => format!("result = {};", expr.to_shader()),
+            SetResult(expr) => format!("result = {};", expr.to_shader_as(Type::U32)),
             IfThenElse {
                 condition,
                 if_true_then,
                 if_false_then,
             } => format!(
                 "if ({}) {{ {} }} else {{ {} }}",
-                condition.to_shader(),
+                condition.to_shader_as(Type::Bool),
                 if_true_then.to_shader(),
                 if_false_then.to_shader()
             ),
@@ -104,6 +203,48 @@ impl Statement {
     }
 }
 
+/**
+ * A complete cellular automaton: the statement that computes the next state of a cell, the
+ * neighborhood whose living cells are counted to produce `Neighbors`, and the number of states a
+ * cell can be in. Only cells in state 1 count as neighbors, higher states are refractory states
+ * that dying cells pass through in Generations rules.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rule {
+    pub neighborhood: Neighborhood,
+    pub states: u32,
+    pub statement: Statement,
+}
+
+impl Rule {
+    /**
+     * A two state rule over the classic 3x3 Moore neighborhood.
+     */
+    pub fn new(statement: Statement) -> Self {
+        Self {
+            neighborhood: Neighborhood::default(),
+            states: 2,
+            statement,
+        }
+    }
+
+    pub fn with_neighborhood(mut self, neighborhood: Neighborhood) -> Self {
+        self.neighborhood = neighborhood;
+        self
+    }
+
+    pub fn with_states(mut self, states: u32) -> Self {
+        self.states = states;
+        self
+    }
+}
+
+impl From<Statement> for Rule {
+    fn from(statement: Statement) -> Self {
+        Self::new(statement)
+    }
+}
+
 pub mod exprs {
     use super::Expr;
     use super::Expr::*;
@@ -116,10 +257,18 @@ pub mod exprs {
         Alive
     }
 
+    pub fn state() -> Expr {
+        State
+    }
+
     pub fn neighbors() -> Expr {
         Neighbors
     }
 
+    pub fn neighbor_mask() -> Expr {
+        NeighborMask
+    }
+
     pub fn gt(lhs: Expr, rhs: Expr) -> Expr {
         Gt(Box::new(lhs), Box::new(rhs))
     }
@@ -147,6 +296,54 @@ pub mod exprs {
     pub fn equal(lhs: Expr, rhs: Expr) -> Expr {
         Equal(Box::new(lhs), Box::new(rhs))
     }
+
+    pub fn not_equal(lhs: Expr, rhs: Expr) -> Expr {
+        not(equal(lhs, rhs))
+    }
+
+    pub fn not(expr: Expr) -> Expr {
+        Not(Box::new(expr))
+    }
+
+    pub fn add(lhs: Expr, rhs: Expr) -> Expr {
+        Add(Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn sub(lhs: Expr, rhs: Expr) -> Expr {
+        Sub(Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn mul(lhs: Expr, rhs: Expr) -> Expr {
+        Mul(Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn modulo(lhs: Expr, rhs: Expr) -> Expr {
+        Mod(Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn min(lhs: Expr, rhs: Expr) -> Expr {
+        Min(Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn max(lhs: Expr, rhs: Expr) -> Expr {
+        Max(Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn shr(lhs: Expr, rhs: Expr) -> Expr {
+        Shr(Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn var(name: &str) -> Expr {
+        Var(name.to_string())
+    }
+
+    pub fn let_in(name: &str, value: Expr, body: Expr) -> Expr {
+        Let {
+            name: name.to_string(),
+            value: Box::new(value),
+            body: Box::new(body),
+        }
+    }
 }
 
 pub mod statements {
@@ -175,7 +372,58 @@ pub mod statements {
 }
 
 pub mod rulesets {
-    use super::{exprs::*, statements::*, Statement};
+    use super::{exprs::*, parse_rulestring, statements::*, Rule, Statement};
+
+    /**
+     * Well known rules and their rulestrings, in `B/S` notation for Life-like rules, `B/S/C`
+     * notation for Generations rules and `R,C,M,S,B,N` notation for Larger than Life rules.
+     */
+    pub const CATALOG: &[(&str, &str)] = &[
+        ("conway", "B3/S23"),
+        ("highlife", "B36/S23"),
+        ("day_and_night", "B3678/S34678"),
+        ("seeds", "B2/S"),
+        ("maze", "B3/S12345"),
+        ("mazectric", "B3/S1234"),
+        ("replicator", "B1357/S1357"),
+        ("life_without_death", "B3/S012345678"),
+        ("2x2", "B36/S125"),
+        ("diamoeba", "B35678/S5678"),
+        ("morley", "B368/S245"),
+        ("anneal", "B4678/S35678"),
+        ("coral", "B3/S45678"),
+        ("34_life", "B34/S34"),
+        ("long_life", "B345/S5"),
+        ("gnarl", "B1/S1"),
+        ("amoeba", "B357/S1358"),
+        ("dry_life", "B37/S23"),
+        ("pedestrian_life", "B38/S23"),
+        ("brians_brain", "B2/S/C3"),
+        ("star_wars", "B2/S345/C4"),
+        ("frogs", "B34/S12/C3"),
+        ("swirl", "B34/S23/C8"),
+        ("hex_life", "B2/S34H"),
+        ("von_neumann_life", "B3/S23V"),
+        ("tlife", "B3/S2-i34q"),
+        ("salad", "B2i34c/S2-i3"),
+        ("bosco", "R5,C0,M1,S34..58,B34..45,NM"),
+        ("majority", "R4,C0,M1,S41..81,B41..81,NM"),
+        ("bugsmovie", "R10,C0,M1,S123..212,B123..170,NM"),
+    ];
+
+    /**
+     * Look up a rule from the catalog by name. Names are case insensitive and spaces or dashes
+     * may be used in place of underscores, e.g. `Day-and-Night`.
+     */
+    pub fn by_name(name: &str) -> Option<Rule> {
+        let name = name.trim().to_lowercase().replace([' ', '-'], "_");
+        CATALOG
+            .iter()
+            .find(|(entry, _)| *entry == name)
+            .map(|(_, rulestring)| {
+                parse_rulestring(rulestring).expect("catalog rulestrings are valid")
+            })
+    }
 
     /**
      * An implementation of conways game of life in
@@ -0,0 +1,360 @@
+use std::fmt;
+
+use super::{Expr, Rule, Statement};
+
+/**
+ * The largest number of states a rule can have. States are stored in a 16 bit float texture,
+ * which represents every integer up to 2048 exactly.
+ */
+pub const MAX_STATES: u32 = 2048;
+
+/**
+ * The wgsl type of an expression. Comparisons and `Alive` are booleans, everything else is an
+ * unsigned integer. The shader generator converts between the two where an expression of one
+ * type is used where the other is expected.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Bool,
+    U32,
+}
+
+impl Type {
+    /**
+     * Convert the wgsl `code` of an expression of type `from` to type `to`. Booleans become 0 or
+     * 1 and integers are true when they are non-zero, the same semantics as the CPU evaluator.
+     */
+    pub fn convert(code: String, from: Type, to: Type) -> String {
+        match (from, to) {
+            (Type::Bool, Type::U32) => format!("u32({})", code),
+            (Type::U32, Type::Bool) => format!("(({}) != 0u)", code),
+            _ => code,
+        }
+    }
+
+    /**
+     * The type of an expression that does not bind or read a variable, given the types of its
+     * operands.
+     */
+    pub(super) fn of(expr: &Expr, operands: &[Type]) -> Type {
+        use Expr::*;
+
+        match expr {
+            Alive | Not(_) | Gt(..) | Gte(..) | Lt(..) | Lte(..) | Equal(..) => Type::Bool,
+            And(..) | Or(..) if operands.iter().all(|ty| *ty == Type::Bool) => Type::Bool,
+            _ => Type::U32,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Bool => write!(f, "bool"),
+            Type::U32 => write!(f, "u32"),
+        }
+    }
+}
+
+/**
+ * A problem with a DSL program that would either fail to compile to wgsl or do something other
+ * than what was written.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckError {
+    UnboundVariable(String),
+    /// A remainder of a division by the constant 0
+    ModuloByZero,
+    /// A constant result that is not a state of the rule. Only `result = <literal>` is checked,
+    /// results computed from the cell can still be out of range.
+    ResultOutOfRange {
+        result: u32,
+        states: u32,
+    },
+    TooManyStates(u32),
+    /// Fewer than the dead and the living state
+    TooFewStates(u32),
+    EmptyNeighborhood,
+    /// The weights of a custom neighborhood add up to more than a `u32` holds
+    NeighborCountOverflow,
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::UnboundVariable(name) => {
+                write!(f, "variable `{name}` is not bound by an enclosing `let`")
+            }
+            CheckError::ModuloByZero => write!(f, "remainder of a division by zero"),
+            CheckError::ResultOutOfRange { result, states } => write!(
+                f,
+                "result {result} is not a valid state, the rule only has states 0 to {}",
+                states.saturating_sub(1)
+            ),
+            CheckError::TooManyStates(states) => write!(
+                f,
+                "{states} states is more than the {MAX_STATES} the canvas can store"
+            ),
+            CheckError::TooFewStates(states) => write!(
+                f,
+                "{states} states is too few, a rule needs at least a dead and a living state"
+            ),
+            CheckError::EmptyNeighborhood => write!(f, "the neighborhood has no cells"),
+            CheckError::NeighborCountOverflow => write!(
+                f,
+                "the weights of the neighborhood add up to more than {}",
+                u32::MAX
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CheckError {}
+
+impl Expr {
+    /**
+     * Assign a type to this expression and every expression in it, returning the type of the
+     * whole expression or every problem found.
+     */
+    pub fn check(&self) -> Result<Type, Vec<CheckError>> {
+        let mut errors = Vec::new();
+        let ty = self.check_in(&mut Vec::new(), &mut errors);
+        if errors.is_empty() {
+            Ok(ty)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /**
+     * The type of this expression with the variables in `scope` bound to the given types,
+     * ignoring any problem found in it.
+     */
+    pub(super) fn type_in(&self, scope: &[(String, Type)]) -> Type {
+        self.check_in(&mut scope.to_vec(), &mut Vec::new())
+    }
+
+    fn check_in(&self, scope: &mut Vec<(String, Type)>, errors: &mut Vec<CheckError>) -> Type {
+        use Expr::*;
+
+        match self {
+            Var(name) => match scope.iter().rev().find(|(bound, _)| bound == name) {
+                Some((_, ty)) => *ty,
+                None => {
+                    errors.push(CheckError::UnboundVariable(name.clone()));
+                    Type::U32
+                }
+            },
+            Let { name, value, body } => {
+                let value = value.check_in(scope, errors);
+                scope.push((name.clone(), value));
+                let body = body.check_in(scope, errors);
+                scope.pop();
+                body
+            }
+            _ => {
+                let operands = self
+                    .operands()
+                    .iter()
+                    .map(|operand| operand.check_in(scope, errors))
+                    .collect::<Vec<_>>();
+                if matches!(self, Mod(_, rhs) if matches!(**rhs, U32(0))) {
+                    errors.push(CheckError::ModuloByZero);
+                }
+                Type::of(self, &operands)
+            }
+        }
+    }
+
+    /**
+     * The direct sub-expressions of an expression that does not bind a variable.
+     */
+    pub(super) fn operands(&self) -> Vec<&Expr> {
+        use Expr::*;
+
+        match self {
+            U32(_) | Alive | State | Neighbors | NeighborMask | Var(_) => vec![],
+            Let { value, body, .. } => vec![value, body],
+            Not(expr) => vec![expr],
+            Add(lhs, rhs)
+            | Sub(lhs, rhs)
+            | Mul(lhs, rhs)
+            | Mod(lhs, rhs)
+            | Min(lhs, rhs)
+            | Max(lhs, rhs)
+            | Shr(lhs, rhs)
+            | Gt(lhs, rhs)
+            | Gte(lhs, rhs)
+            | Lt(lhs, rhs)
+            | Lte(lhs, rhs)
+            | And(lhs, rhs)
+            | Or(lhs, rhs)
+            | Equal(lhs, rhs) => vec![lhs, rhs],
+        }
+    }
+}
+
+impl Statement {
+    fn check_in(&self, states: u32, errors: &mut Vec<CheckError>) {
+        use Statement::*;
+
+        let mut check_expr = |expr: &Expr| {
+            if let Err(expr_errors) = expr.check() {
+                errors.extend(expr_errors);
+            }
+        };
+
+        match self {
+            Void => {}
+            SetResult(expr) => {
+                check_expr(expr);
+                if let Expr::U32(result) = expr {
+                    if *result >= states {
+                        errors.push(CheckError::ResultOutOfRange {
+                            result: *result,
+                            states,
+                        });
+                    }
+                }
+            }
+            IfThenElse {
+                condition,
+                if_true_then,
+                if_false_then,
+            } => {
+                check_expr(condition);
+                if_true_then.check_in(states, errors);
+                if_false_then.check_in(states, errors);
+            }
+        }
+    }
+}
+
+impl Rule {
+    /**
+     * Check the whole rule before it is turned into wgsl, returning every problem found.
+     */
+    pub fn check(&self) -> Result<(), Vec<CheckError>> {
+        let mut errors = Vec::new();
+        if self.states > MAX_STATES {
+            errors.push(CheckError::TooManyStates(self.states));
+        }
+        if self.states < 2 {
+            errors.push(CheckError::TooFewStates(self.states));
+        }
+        if self.neighborhood.offsets().is_empty() {
+            errors.push(CheckError::EmptyNeighborhood);
+        }
+        if self.neighborhood.checked_max_count().is_none() {
+            errors.push(CheckError::NeighborCountOverflow);
+        }
+        self.statement.check_in(self.states, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::{exprs::*, statements::*, NeighborOffset, Neighborhood};
+
+    #[test]
+    fn types() {
+        assert_eq!(alive().check(), Ok(Type::Bool));
+        assert_eq!(gt(neighbors(), const_u32(2)).check(), Ok(Type::Bool));
+        assert_eq!(and(alive(), not(alive())).check(), Ok(Type::Bool));
+        // With an integer operand `And` and `Or` work bit by bit
+        assert_eq!(or(alive(), const_u32(2)).check(), Ok(Type::U32));
+        assert_eq!(add(alive(), alive()).check(), Ok(Type::U32));
+        assert_eq!(
+            let_in("a", alive(), and(var("a"), alive())).check(),
+            Ok(Type::Bool)
+        );
+    }
+
+    #[test]
+    fn modulo_by_zero_checks_both_operands() {
+        assert_eq!(
+            modulo(var("a"), const_u32(0)).check(),
+            Err(vec![
+                CheckError::UnboundVariable("a".to_string()),
+                CheckError::ModuloByZero
+            ])
+        );
+        assert_eq!(
+            modulo(
+                const_u32(4),
+                let_in("zero", const_u32(0), modulo(var("zero"), const_u32(0)))
+            )
+            .check(),
+            Err(vec![CheckError::ModuloByZero])
+        );
+        assert_eq!(modulo(neighbors(), const_u32(3)).check(), Ok(Type::U32));
+    }
+
+    #[test]
+    fn constant_results_must_be_states() {
+        let rule = Rule::new(if_then_else(
+            alive(),
+            set_result(const_u32(2)),
+            set_result(const_u32(1)),
+        ));
+        assert_eq!(
+            rule.check(),
+            Err(vec![CheckError::ResultOutOfRange {
+                result: 2,
+                states: 2
+            }])
+        );
+        assert_eq!(rule.clone().with_states(3).check(), Ok(()));
+
+        // Only literals are checked
+        let rule = Rule::new(set_result(add(neighbors(), const_u32(5))));
+        assert_eq!(rule.check(), Ok(()));
+    }
+
+    #[test]
+    fn rule_limits() {
+        let rule = Rule::new(set_result(const_u32(0)))
+            .with_states(MAX_STATES + 1)
+            .with_neighborhood(Neighborhood::Custom(vec![]));
+        assert_eq!(
+            rule.check(),
+            Err(vec![
+                CheckError::TooManyStates(MAX_STATES + 1),
+                CheckError::EmptyNeighborhood
+            ])
+        );
+
+        let heavy = [u32::MAX, 1].map(|weight| NeighborOffset {
+            x: 0,
+            y: -1,
+            weight,
+        });
+        let rule = Rule::new(set_result(const_u32(0)))
+            .with_neighborhood(Neighborhood::Custom(heavy.to_vec()));
+        assert_eq!(rule.check(), Err(vec![CheckError::NeighborCountOverflow]));
+
+        // Deserialized rules can have any number of states
+        let rule = Rule::new(set_result(const_u32(0)));
+        assert_eq!(
+            rule.clone().with_states(1).check(),
+            Err(vec![CheckError::TooFewStates(1)])
+        );
+        assert_eq!(
+            rule.with_states(0).check(),
+            Err(vec![
+                CheckError::TooFewStates(0),
+                CheckError::ResultOutOfRange {
+                    result: 0,
+                    states: 0
+                }
+            ])
+        );
+    }
+}
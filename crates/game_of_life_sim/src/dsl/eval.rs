@@ -0,0 +1,166 @@
+use super::{Expr, Statement};
+
+impl Expr {
+    /**
+     * Evaluate this expression on the CPU for a cell with the given state. This mirrors the
+     * semantics of the wgsl generated by `to_shader`: comparisons produce 0 or 1, `And` and `Or`
+     * are bitwise, a living cell reads as 1 and arithmetic wraps around like wgsl's `u32`.
+     *
+     * Like the shader generator this expects an expression that passed `check` (or a rule that
+     * passed `Rule::check`): evaluating a `Var` that no enclosing `Let` binds panics.
+     */
+    pub fn eval(&self, alive: bool, neighbors: u32) -> u32 {
+        self.eval_state(alive as u32, neighbors)
+    }
+
+    /**
+     * Evaluate this expression for a cell in any state of a multi-state rule, where state 1 is
+     * the living (firing) state. `NeighborMask` reads as 0, use `eval_cell` for rules that read
+     * the shape of the neighborhood.
+     */
+    pub fn eval_state(&self, state: u32, neighbors: u32) -> u32 {
+        self.eval_cell(state, neighbors, 0)
+    }
+
+    /**
+     * Evaluate this expression for a cell with every input given, including the mask of its
+     * living neighbors.
+     */
+    pub fn eval_cell(&self, state: u32, neighbors: u32, neighbor_mask: u32) -> u32 {
+        self.eval_in(&(state, neighbors, neighbor_mask), &mut Vec::new())
+    }
+
+    fn eval_in(&self, inputs: &(u32, u32, u32), scope: &mut Vec<(String, u32)>) -> u32 {
+        use Expr::*;
+
+        let (state, neighbors, neighbor_mask) = *inputs;
+        let mut eval = |expr: &Expr| expr.eval_in(inputs, scope);
+
+        match self {
+            U32(val) => *val,
+            Alive => (state == 1) as u32,
+            State => state,
+            Neighbors => neighbors,
+            NeighborMask => neighbor_mask,
+            Var(name) => match scope.iter().rev().find(|(bound, _)| bound == name) {
+                Some((_, value)) => *value,
+                None => panic!("variable `{name}` is not bound by an enclosing `let`"),
+            },
+            Let { name, value, body } => {
+                let value = eval(value);
+                scope.push((name.clone(), value));
+                let body = body.eval_in(inputs, scope);
+                scope.pop();
+                body
+            }
+            Not(expr) => (eval(expr) == 0) as u32,
+            Add(lhs, rhs) => eval(lhs).wrapping_add(eval(rhs)),
+            Sub(lhs, rhs) => eval(lhs).wrapping_sub(eval(rhs)),
+            Mul(lhs, rhs) => eval(lhs).wrapping_mul(eval(rhs)),
+            // wgsl defines the remainder of a division by zero as 0
+            Mod(lhs, rhs) => eval(lhs).checked_rem(eval(rhs)).unwrap_or(0),
+            Min(lhs, rhs) => eval(lhs).min(eval(rhs)),
+            Max(lhs, rhs) => eval(lhs).max(eval(rhs)),
+            // wgsl only uses the lowest 5 bits of the shift amount, so does `wrapping_shr`
+            Shr(lhs, rhs) => eval(lhs).wrapping_shr(eval(rhs)),
+            Gt(lhs, rhs) => (eval(lhs) > eval(rhs)) as u32,
+            Gte(lhs, rhs) => (eval(lhs) >= eval(rhs)) as u32,
+            Lt(lhs, rhs) => (eval(lhs) < eval(rhs)) as u32,
+            Lte(lhs, rhs) => (eval(lhs) <= eval(rhs)) as u32,
+            And(lhs, rhs) => eval(lhs) & eval(rhs),
+            Or(lhs, rhs) => eval(lhs) | eval(rhs),
+            Equal(lhs, rhs) => (eval(lhs) == eval(rhs)) as u32,
+        }
+    }
+}
+
+impl Statement {
+    /**
+     * Run this statement on the CPU for a cell with the given state and return the value it
+     * leaves in `result`. Like the shader, `result` starts out as 0 and a condition is true when
+     * it is non-zero.
+     */
+    pub fn eval(&self, alive: bool, neighbors: u32) -> u32 {
+        self.eval_state(alive as u32, neighbors)
+    }
+
+    /**
+     * Run this statement for a cell in any state of a multi-state rule and return the state it
+     * moves to. `NeighborMask` reads as 0.
+     */
+    pub fn eval_state(&self, state: u32, neighbors: u32) -> u32 {
+        self.eval_cell(state, neighbors, 0)
+    }
+
+    /**
+     * Run this statement for a cell with every input given, including the mask of its living
+     * neighbors.
+     */
+    pub fn eval_cell(&self, state: u32, neighbors: u32, neighbor_mask: u32) -> u32 {
+        let mut result = 0;
+        self.execute(state, neighbors, neighbor_mask, &mut result);
+        result
+    }
+
+    fn execute(&self, state: u32, neighbors: u32, neighbor_mask: u32, result: &mut u32) {
+        use Statement::*;
+
+        match self {
+            Void => {}
+            SetResult(expr) => *result = expr.eval_cell(state, neighbors, neighbor_mask),
+            IfThenElse {
+                condition,
+                if_true_then,
+                if_false_then,
+            } => {
+                let branch = if condition.eval_cell(state, neighbors, neighbor_mask) != 0 {
+                    if_true_then
+                } else {
+                    if_false_then
+                };
+                branch.execute(state, neighbors, neighbor_mask, result);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dsl::{exprs::*, statements::*, CheckError, Rule};
+
+    #[test]
+    fn let_bindings_shadow() {
+        let expr = let_in(
+            "a",
+            neighbors(),
+            add(let_in("a", const_u32(10), var("a")), var("a")),
+        );
+        assert_eq!(expr.eval(false, 3), 13);
+    }
+
+    #[test]
+    fn check_rejects_what_eval_cannot_run() {
+        let expr = add(let_in("a", const_u32(1), var("a")), var("a"));
+        assert_eq!(
+            expr.check(),
+            Err(vec![CheckError::UnboundVariable("a".to_string())])
+        );
+        assert_eq!(
+            Rule::new(set_result(expr)).check(),
+            Err(vec![CheckError::UnboundVariable("a".to_string())])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "variable `a` is not bound by an enclosing `let`")]
+    fn unbound_variable_panics() {
+        add(let_in("a", const_u32(1), var("a")), var("a")).eval(true, 0);
+    }
+
+    #[test]
+    fn statements_start_from_zero() {
+        let statement = if_then_else(alive(), set_result(const_u32(1)), void());
+        assert_eq!(statement.eval(true, 0), 1);
+        assert_eq!(statement.eval(false, 0), 0);
+    }
+}
@@ -0,0 +1,461 @@
+use super::{exprs::*, statements::*, Expr, ParseError, Rule};
+
+/**
+ * The letters of the classes of each neighbor count from 0 to 4 in Hensel notation, in the order
+ * Golly writes them. Counts 5 to 8 use the letters of `8 - count`.
+ */
+const LETTERS: [&str; 5] = ["", "ce", "ceaikn", "ceaiknjqry", "ceaiknjqrtwyz"];
+
+/**
+ * One configuration of each class in `LETTERS`, as Golly numbers the 3x3 block: bit 0 is the top
+ * left cell, bit 4 the center and bit 8 the bottom right cell.
+ */
+const REPRESENTATIVES: [&[u32]; 5] = [
+    &[0],
+    &[1, 2],
+    &[5, 10, 3, 40, 33, 68],
+    &[69, 42, 11, 7, 98, 13, 14, 70, 41, 97],
+    &[325, 170, 15, 45, 99, 71, 106, 102, 43, 101, 105, 78, 108],
+];
+
+/**
+ * The letters of the classes of configurations with `count` living neighbors, empty for 0 and 8
+ * where there is only one configuration.
+ */
+pub fn letters(count: u32) -> &'static str {
+    LETTERS[count.min(8 - count.min(8)) as usize]
+}
+
+/**
+ * Whether `c` is the letter of a class of some neighbor count.
+ */
+pub fn is_letter(c: char) -> bool {
+    LETTERS[4].contains(c)
+}
+
+/**
+ * Every class of neighbor configurations as its neighbor count and letter, 51 in total. Counts
+ * with a single configuration have no letter.
+ */
+pub fn classes() -> Vec<(u32, Option<char>)> {
+    (0..=8)
+        .flat_map(|count| match letters(count) {
+            "" => vec![(count, None)],
+            letters => letters
+                .chars()
+                .map(|letter| (count, Some(letter)))
+                .collect(),
+        })
+        .collect()
+}
+
+/**
+ * Every neighbor mask in the class of `count` and `letter`, in the bit order of
+ * `Expr::NeighborMask`. Returns an empty list for a letter the count doesn't have.
+ */
+pub fn class_masks(count: u32, letter: Option<char>) -> Vec<u32> {
+    let representative = match (count, letter) {
+        (0, None) => 0,
+        (8, None) => 255,
+        (1..=7, Some(letter)) => {
+            let Some(index) = letters(count).find(letter) else {
+                return vec![];
+            };
+            let mask = from_golly(REPRESENTATIVES[count.min(8 - count) as usize][index]);
+            if count > 4 {
+                !mask & 255
+            } else {
+                mask
+            }
+        }
+        _ => return vec![],
+    };
+
+    let mut masks = symmetries(representative);
+    masks.sort_unstable();
+    masks.dedup();
+    masks
+}
+
+/**
+ * The class of a neighbor mask as its neighbor count and letter.
+ */
+pub fn classify(mask: u32) -> (u32, Option<char>) {
+    let mask = mask & 255;
+    let count = mask.count_ones();
+    let letter = letters(count)
+        .chars()
+        .find(|letter| class_masks(count, Some(*letter)).contains(&mask));
+    (count, letter)
+}
+
+/**
+ * Convert a Golly numbered 3x3 block to a neighbor mask by dropping the center bit.
+ */
+fn from_golly(block: u32) -> u32 {
+    (block & 0b1111) | (block >> 5) << 4
+}
+
+/**
+ * The mask as seen through each of the 8 rotations and reflections of the square.
+ */
+fn symmetries(mask: u32) -> Vec<u32> {
+    let offsets = (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+        .filter(|offset| *offset != (0, 0))
+        .collect::<Vec<(i32, i32)>>();
+    let bit = |offset: (i32, i32)| offsets.iter().position(|o| *o == offset).unwrap();
+
+    let transform = |map: &dyn Fn((i32, i32)) -> (i32, i32)| {
+        offsets
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| mask & 1 << index != 0)
+            .fold(0, |result, (_, offset)| result | 1 << bit(map(*offset)))
+    };
+
+    (0..8)
+        .map(|symmetry| {
+            transform(&|(mut x, mut y)| {
+                for _ in 0..symmetry % 4 {
+                    (x, y) = (-y, x);
+                }
+                if symmetry >= 4 {
+                    x = -x;
+                }
+                (x, y)
+            })
+        })
+        .collect()
+}
+
+/**
+ * An isotropic non-totalistic rule over the 3x3 Moore neighborhood, where cells are born or
+ * survive depending on the shape of their living neighbors rather than only their number. The
+ * sets hold one bit per neighbor mask, bit `mask % 32` of word `mask / 32`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IsotropicRule {
+    pub birth: [u32; 8],
+    pub survival: [u32; 8],
+}
+
+impl IsotropicRule {
+    /**
+     * Parse a rulestring in Hensel notation, e.g. `B3/S2-i34q`. Each neighbor count may be
+     * followed by the letters of the classes it applies to, or by `-` and the letters of the
+     * classes it doesn't apply to. A count on its own applies to every class.
+     */
+    pub fn parse(rulestring: &str) -> Result<Self, ParseError> {
+        let rulestring = rulestring.trim();
+        let error = |message: String, index: usize| ParseError {
+            message,
+            line: 1,
+            column: index + 1,
+        };
+
+        let mut rule = IsotropicRule::default();
+        let (mut seen_birth, mut seen_survival) = (false, false);
+        let mut chars = rulestring.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            let (set, seen) = match c.to_ascii_uppercase() {
+                'B' => (&mut rule.birth, &mut seen_birth),
+                'S' => (&mut rule.survival, &mut seen_survival),
+                '/' => continue,
+                _ => return Err(error(format!("unexpected character `{c}`"), index)),
+            };
+            if *seen {
+                return Err(error(format!("`{c}` appears more than once"), index));
+            }
+            *seen = true;
+
+            while let Some(&(index, digit)) = chars.peek() {
+                let Some(count) = digit.to_digit(10) else {
+                    break;
+                };
+                if count > 8 {
+                    return Err(error(
+                        format!("`{digit}` is not a neighbor count between 0 and 8"),
+                        index,
+                    ));
+                }
+                chars.next();
+
+                let negated = chars.next_if(|&(_, c)| c == '-').is_some();
+                let mut named = Vec::new();
+                while let Some((index, letter)) = chars.next_if(|&(_, c)| is_letter(c)) {
+                    if !letters(count).contains(letter) {
+                        return Err(error(
+                            format!("`{letter}` is not a class of {count} neighbors"),
+                            index,
+                        ));
+                    }
+                    named.push(letter);
+                }
+
+                for (_, letter) in classes().into_iter().filter(|(n, _)| *n == count) {
+                    let included = match letter {
+                        Some(letter) if !named.is_empty() => named.contains(&letter) != negated,
+                        _ => !negated || named.is_empty(),
+                    };
+                    if included {
+                        for mask in class_masks(count, letter) {
+                            set[mask as usize / 32] |= 1 << (mask % 32);
+                        }
+                    }
+                }
+            }
+        }
+
+        if !seen_birth || !seen_survival {
+            return Err(error(
+                "expected both a `B` and an `S` section".to_string(),
+                0,
+            ));
+        }
+
+        Ok(rule)
+    }
+
+    /**
+     * Format this rule in Hensel notation, using whichever of the letters of the included or the
+     * excluded classes is shorter for each count.
+     */
+    pub fn to_rulestring(&self) -> String {
+        let section = |set: &[u32; 8]| {
+            let contains = |mask: u32| set[mask as usize / 32] & 1 << (mask % 32) != 0;
+            let mut section = String::new();
+            for count in 0..=8 {
+                let classes = classes()
+                    .into_iter()
+                    .filter(|(n, _)| *n == count)
+                    .map(|(_, letter)| {
+                        let included = class_masks(count, letter).into_iter().all(contains);
+                        (letter, included)
+                    })
+                    .collect::<Vec<_>>();
+                let letters = |included: bool| -> String {
+                    classes
+                        .iter()
+                        .filter(|(_, i)| *i == included)
+                        .filter_map(|(letter, _)| *letter)
+                        .collect()
+                };
+                let (included, excluded) = (letters(true), letters(false));
+
+                if classes.iter().all(|(_, included)| !included) {
+                    continue;
+                }
+                section.push(char::from(b'0' + count as u8));
+                if excluded.is_empty() {
+                    continue;
+                }
+                if excluded.len() < included.len() {
+                    section.push('-');
+                    section.push_str(&excluded);
+                } else {
+                    section.push_str(&included);
+                }
+            }
+            section
+        };
+        format!("B{}/S{}", section(&self.birth), section(&self.survival))
+    }
+
+    /**
+     * Build the rule, a living cell survives if its neighbor mask is in the survival set,
+     * otherwise a dead cell is born if its neighbor mask is in the birth set.
+     */
+    pub fn to_rule(&self) -> Rule {
+        Rule::new(if_then_else(
+            alive(),
+            set_result(mask_in(&self.survival)),
+            set_result(mask_in(&self.birth)),
+        ))
+    }
+}
+
+/**
+ * An expression that is 1 when the neighbor mask is in `set`. The word of the set that holds the
+ * mask is picked with a sum of comparisons and shifted to the bit of the mask.
+ */
+fn mask_in(set: &[u32; 8]) -> Expr {
+    if set.iter().all(|word| *word == 0) {
+        return const_u32(0);
+    }
+    if set.iter().all(|word| *word == u32::MAX) {
+        return const_u32(1);
+    }
+
+    let index = shr(var("mask"), const_u32(5));
+    let word = (0..8u32)
+        .filter(|&i| set[i as usize] != 0)
+        .map(|i| {
+            mul(
+                const_u32(set[i as usize]),
+                equal(index.clone(), const_u32(i)),
+            )
+        })
+        .reduce(add)
+        .unwrap();
+    let bit = and(shr(word, modulo(var("mask"), const_u32(32))), const_u32(1));
+    let_in("mask", neighbor_mask(), bit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * Golly's table of one configuration per class for 1 to 4 neighbors, as 3x3 blocks numbered
+     * row by row from bit 0 at the top left to bit 8 at the bottom right. Counts 5 to 7 are the
+     * complements of the configurations of `8 - count`.
+     */
+    const GOLLY: [(u32, &str, &[u32]); 4] = [
+        (1, "ce", &[1, 2]),
+        (2, "ceaikn", &[5, 10, 3, 40, 33, 68]),
+        (3, "ceaiknjqry", &[69, 42, 11, 7, 98, 13, 14, 70, 41, 97]),
+        (
+            4,
+            "ceaiknjqrtwyz",
+            &[325, 170, 15, 45, 99, 71, 106, 102, 43, 101, 105, 78, 108],
+        ),
+    ];
+
+    /**
+     * The living cells of a 3x3 block around its center, without the center.
+     */
+    fn block_cells(block: u32) -> Vec<(i32, i32)> {
+        (0..9)
+            .filter(|&bit| bit != 4 && block & 1 << bit != 0)
+            .map(|bit| (bit % 3 - 1, bit / 3 - 1))
+            .collect()
+    }
+
+    /**
+     * The neighbor mask of the living cells around a center.
+     */
+    fn mask_of(cells: &[(i32, i32)]) -> u32 {
+        cells
+            .iter()
+            .map(|&(x, y)| {
+                let bit = (y + 1) * 3 + x + 1;
+                1 << if bit > 4 { bit - 1 } else { bit }
+            })
+            .sum()
+    }
+
+    /**
+     * Every rotation and reflection of the cells, as neighbor masks.
+     */
+    fn orientations(cells: &[(i32, i32)]) -> Vec<u32> {
+        let mut masks = Vec::new();
+        let mut cells = cells.to_vec();
+        for _ in 0..4 {
+            cells = cells.iter().map(|&(x, y)| (y, -x)).collect();
+            masks.push(mask_of(&cells));
+            let mirrored = cells.iter().map(|&(x, y)| (-x, y)).collect::<Vec<_>>();
+            masks.push(mask_of(&mirrored));
+        }
+        masks
+    }
+
+    /**
+     * The class of every neighbor mask according to Golly's table.
+     */
+    fn golly_classes() -> Vec<Option<(u32, Option<char>)>> {
+        let mut classes = vec![None; 256];
+        classes[0] = Some((0, None));
+        classes[255] = Some((8, None));
+        for (count, letters, blocks) in GOLLY {
+            for (letter, &block) in letters.chars().zip(blocks) {
+                for mask in orientations(&block_cells(block)) {
+                    classes[mask as usize] = Some((count, Some(letter)));
+                    // The complement of a configuration of 4 can be in another class of 4
+                    if count < 4 {
+                        classes[(!mask & 255) as usize] = Some((8 - count, Some(letter)));
+                    }
+                }
+            }
+        }
+        classes
+    }
+
+    #[test]
+    fn every_mask_is_in_its_golly_class() {
+        let golly = golly_classes();
+        for mask in 0..256 {
+            let class = golly[mask as usize].expect("Golly's table covers every mask");
+            assert_eq!(classify(mask), class, "mask {mask:#010b}");
+            assert!(class_masks(class.0, class.1).contains(&mask));
+        }
+
+        let mut distinct = golly.into_iter().flatten().collect::<Vec<_>>();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 51);
+        assert_eq!(distinct, {
+            let mut classes = classes();
+            classes.sort_unstable();
+            classes
+        });
+    }
+
+    #[test]
+    fn classes_partition_the_masks() {
+        let mut masks = classes()
+            .into_iter()
+            .flat_map(|(count, letter)| class_masks(count, letter))
+            .collect::<Vec<_>>();
+        masks.sort_unstable();
+        assert_eq!(masks, (0..256).collect::<Vec<_>>());
+        assert!(class_masks(2, Some('q')).is_empty());
+        assert!(class_masks(0, Some('c')).is_empty());
+    }
+
+    #[test]
+    fn single_class_rules_fire_only_for_their_class() {
+        for class @ (count, letter) in classes() {
+            let letter = letter.map(String::from).unwrap_or_default();
+            let birth = IsotropicRule::parse(&format!("B{count}{letter}/S"))
+                .unwrap()
+                .to_rule();
+            let survival = IsotropicRule::parse(&format!("B/S{count}{letter}"))
+                .unwrap()
+                .to_rule();
+            for mask in 0..256 {
+                let expected = (classify(mask) == class) as u32;
+                let neighbors = mask.count_ones();
+                assert_eq!(
+                    birth.statement.eval_cell(0, neighbors, mask),
+                    expected,
+                    "B{count}{letter} with mask {mask:#010b}"
+                );
+                assert_eq!(birth.statement.eval_cell(1, neighbors, mask), 0);
+                assert_eq!(
+                    survival.statement.eval_cell(1, neighbors, mask),
+                    expected,
+                    "S{count}{letter} with mask {mask:#010b}"
+                );
+                assert_eq!(survival.statement.eval_cell(0, neighbors, mask), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn rulestrings_round_trip() {
+        for rulestring in ["B3/S2-i34q", "B2i34c/S2-i3", "B2ce3-ay/S12k", "B/S"] {
+            let rule = IsotropicRule::parse(rulestring).unwrap();
+            assert_eq!(IsotropicRule::parse(&rule.to_rulestring()).unwrap(), rule);
+        }
+        assert_eq!(
+            IsotropicRule::parse("B3/S23").unwrap().to_rulestring(),
+            "B3/S23"
+        );
+        let err = IsotropicRule::parse("B2q/S").unwrap_err();
+        assert_eq!(
+            (err.column, err.message.as_str()),
+            (3, "`q` is not a class of 2 neighbors")
+        );
+    }
+}
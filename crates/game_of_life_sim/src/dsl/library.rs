@@ -0,0 +1,281 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use super::{parse_rule, parse_rulestring, rulesets, ParseError, Rule};
+use crate::shaders::{default_source, AssetSource};
+
+/**
+ * The extensions of the files a rule can be stored in, the textual syntax, RON and JSON. Rules
+ * are looked up by name in this order.
+ */
+pub const RULE_EXTENSIONS: &[&str] = &["rule", "ron", "json"];
+
+/**
+ * An error produced while reading or writing a rule file.
+ */
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    Parse(ParseError),
+    Serde(String),
+    UnknownExtension(PathBuf),
+    NotFound(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "{err}"),
+            LoadError::Parse(err) => write!(f, "{err}"),
+            LoadError::Serde(err) => write!(f, "{err}"),
+            LoadError::UnknownExtension(path) => write!(
+                f,
+                "{} is not a rule file, expected one of the extensions {}",
+                path.display(),
+                RULE_EXTENSIONS.join(", ")
+            ),
+            LoadError::NotFound(name) => write!(f, "there is no rule named `{name}`"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<std::io::Error> for LoadError {
+    fn from(err: std::io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(err: ParseError) -> Self {
+        LoadError::Parse(err)
+    }
+}
+
+/**
+ * The directory of the rule library in the assets. The library is read through an
+ * `AssetSource` like the shaders, so it is embedded in the binary along with them.
+ */
+pub const RULES_DIR: &str = "rules";
+
+/**
+ * The names of the rules in the library of `assets`, sorted and without extensions.
+ */
+pub fn library_rules(assets: &dyn AssetSource) -> io::Result<Vec<String>> {
+    let mut names = assets
+        .files()?
+        .into_iter()
+        .filter_map(|file| {
+            let (name, extension) = file
+                .strip_prefix(RULES_DIR)?
+                .strip_prefix('/')?
+                .rsplit_once('.')?;
+            RULE_EXTENSIONS
+                .contains(&extension)
+                .then(|| name.to_string())
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/**
+ * Load a rule from the rule library of `shaders::default_source` by the name of its file without
+ * the extension, e.g. `weighted_life` for `assets/rules/weighted_life.rule`.
+ */
+pub fn load_rule(name: &str) -> Result<Rule, LoadError> {
+    load_rule_from(&*default_source(), name)
+}
+
+/**
+ * Load a rule from the rule library of `assets`, see `load_rule`.
+ */
+pub fn load_rule_from(assets: &dyn AssetSource, name: &str) -> Result<Rule, LoadError> {
+    for extension in RULE_EXTENSIONS {
+        match assets.read(&format!("{RULES_DIR}/{name}.{extension}")) {
+            Ok(source) => return parse_source(&source, extension),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Err(LoadError::NotFound(name.to_string()))
+}
+
+/**
+ * Find a rule the way a user names it: the name of a rule in `rulesets::CATALOG` or in the rule
+ * library, a rulestring such as `B36/S23`, or the path to a rule file. A name that looks like a
+ * rulestring and is not a file fails with the reason it is not a valid rulestring.
+ */
+pub fn find_rule(name: &str) -> Result<Rule, LoadError> {
+    if let Some(rule) = rulesets::by_name(name) {
+        return Ok(rule);
+    }
+    let rulestring_error = match parse_rulestring(name) {
+        Ok(rule) => return Ok(rule),
+        Err(err) => err,
+    };
+
+    match load_rule(name) {
+        Err(LoadError::NotFound(_)) if looks_like_rulestring(name) && !Path::new(name).exists() => {
+            Err(LoadError::Parse(rulestring_error))
+        }
+        Err(LoadError::NotFound(_)) => read_rule(name),
+        result => result,
+    }
+}
+
+/**
+ * Whether a name is meant as a rulestring rather than a file: it has no rule file extension and
+ * has a `/` between its sections or starts like `B3/S23` and `R5,C0,...` do.
+ */
+fn looks_like_rulestring(name: &str) -> bool {
+    let name = name.trim();
+    extension(Path::new(name)).is_err()
+        && (name.contains('/') || name.starts_with(['B', 'b', 'S', 's', 'R', 'r']))
+}
+
+/**
+ * Read a rule from a file, the format is picked by the extension of the file.
+ */
+pub fn read_rule(path: impl AsRef<Path>) -> Result<Rule, LoadError> {
+    let path = path.as_ref();
+    let extension = extension(path)?;
+    parse_source(&std::fs::read_to_string(path)?, extension)
+}
+
+/**
+ * Parse the contents of a rule file with the given extension.
+ */
+fn parse_source(source: &str, extension: &str) -> Result<Rule, LoadError> {
+    match extension {
+        "rule" => Ok(parse_rule(source)?),
+        "ron" => ron::from_str(source).map_err(|err| LoadError::Serde(err.to_string())),
+        _ => serde_json::from_str(source).map_err(|err| LoadError::Serde(err.to_string())),
+    }
+}
+
+/**
+ * Write a rule to a file, the format is picked by the extension of the file.
+ */
+pub fn write_rule(path: impl AsRef<Path>, rule: &Rule) -> Result<(), LoadError> {
+    let path = path.as_ref();
+    let contents = match extension(path)? {
+        "rule" => rule.to_string(),
+        "ron" => ron::ser::to_string_pretty(rule, ron::ser::PrettyConfig::default())
+            .map_err(|err| LoadError::Serde(err.to_string()))?,
+        _ => serde_json::to_string_pretty(rule).map_err(|err| LoadError::Serde(err.to_string()))?,
+    };
+    Ok(std::fs::write(path, contents)?)
+}
+
+fn extension(path: &Path) -> Result<&str, LoadError> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| RULE_EXTENSIONS.contains(extension))
+        .ok_or_else(|| LoadError::UnknownExtension(path.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shaders::{EmbeddedAssets, FileSystemAssets};
+
+    /// The catalog and every rule of the library
+    fn every_rule() -> Vec<(String, Rule)> {
+        let assets = FileSystemAssets::default();
+        let catalog = rulesets::CATALOG
+            .iter()
+            .map(|(name, _)| (name.to_string(), rulesets::by_name(name).unwrap()));
+        let library = library_rules(&assets).unwrap().into_iter().map(|name| {
+            let rule = load_rule_from(&assets, &name).unwrap();
+            (name, rule)
+        });
+        catalog.chain(library).collect()
+    }
+
+    #[test]
+    fn rules_round_trip_through_ron_and_json() {
+        for (name, rule) in every_rule() {
+            let ron = ron::ser::to_string_pretty(&rule, ron::ser::PrettyConfig::default()).unwrap();
+            assert_eq!(ron::from_str::<Rule>(&ron).unwrap(), rule, "{name} as RON");
+
+            let json = serde_json::to_string_pretty(&rule).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Rule>(&json).unwrap(),
+                rule,
+                "{name} as JSON"
+            );
+        }
+    }
+
+    #[test]
+    fn every_library_file_is_listed_and_loads() {
+        let assets = FileSystemAssets::default();
+        let files = std::fs::read_dir(Path::new(crate::shaders::ASSET_DIR).join(RULES_DIR))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        let names = library_rules(&assets).unwrap();
+        assert_eq!(names.len(), files.len());
+        for path in files {
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            assert!(names.iter().any(|listed| listed == name), "{name}");
+            assert_eq!(
+                read_rule(&path).unwrap(),
+                load_rule_from(&assets, name).unwrap()
+            );
+        }
+
+        // The same library is baked into the binary
+        let embedded = EmbeddedAssets::default();
+        assert_eq!(library_rules(&embedded).unwrap(), names);
+        for name in &names {
+            assert_eq!(
+                load_rule_from(&embedded, name).unwrap(),
+                load_rule_from(&assets, name).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn find_rule_by_every_kind_of_name() {
+        assert_eq!(
+            find_rule("Day-and-Night").unwrap(),
+            rulesets::by_name("day_and_night").unwrap()
+        );
+        assert_eq!(
+            find_rule("B36/S23").unwrap(),
+            rulesets::by_name("highlife").unwrap()
+        );
+        assert_eq!(find_rule("fredkin").unwrap(), load_rule("fredkin").unwrap());
+        let path = Path::new(crate::shaders::ASSET_DIR).join("rules/fredkin.rule");
+        assert_eq!(
+            find_rule(path.to_str().unwrap()).unwrap(),
+            load_rule("fredkin").unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_rulestrings_report_why() {
+        for name in ["B3/S2x", "B3/S23/C1", "S23", "R5,C0,X1"] {
+            match find_rule(name) {
+                Err(LoadError::Parse(err)) => {
+                    assert_eq!(Err(err), parse_rulestring(name).map(|_| ()), "{name}")
+                }
+                result => panic!("{name} gave {result:?}"),
+            }
+        }
+        assert!(matches!(
+            find_rule("no_such_rule"),
+            Err(LoadError::UnknownExtension(_))
+        ));
+        assert!(matches!(
+            find_rule("rules/missing.rule"),
+            Err(LoadError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+
+/**
+ * A cell in a neighborhood, relative to the cell being updated. Living cells at this offset add
+ * `weight` to the neighbor count.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NeighborOffset {
+    pub x: i32,
+    pub y: i32,
+    pub weight: u32,
+}
+
+/**
+ * The set of cells that are counted as neighbors of a cell. The shader generator turns this into
+ * the body of `count_neighbors` in `game_of_life.wgsl`, and the CPU grid counts the same offsets.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Neighborhood {
+    /// Every cell within a square of the given radius, radius 1 is the classic 3x3 neighborhood
+    Moore { radius: u32 },
+    /// Every cell within the given manhattan distance
+    VonNeumann { radius: u32 },
+    /// The six neighbors of a hexagonal grid drawn on a square grid by skewing it, the same
+    /// layout Golly uses for its `H` rules
+    Hexagonal,
+    /// An explicit weighted list of offsets
+    Custom(Vec<NeighborOffset>),
+}
+
+impl Default for Neighborhood {
+    fn default() -> Self {
+        Neighborhood::Moore { radius: 1 }
+    }
+}
+
+impl Neighborhood {
+    /**
+     * All the offsets in this neighborhood with their weight. The cell itself is never part of a
+     * Moore, von Neumann or hexagonal neighborhood.
+     */
+    pub fn offsets(&self) -> Vec<NeighborOffset> {
+        let square = |radius: u32, keep: &dyn Fn(i32, i32) -> bool| {
+            let radius = radius as i32;
+            let mut offsets = Vec::new();
+            for y in -radius..=radius {
+                for x in -radius..=radius {
+                    if (x != 0 || y != 0) && keep(x, y) {
+                        offsets.push(NeighborOffset { x, y, weight: 1 });
+                    }
+                }
+            }
+            offsets
+        };
+
+        match self {
+            Neighborhood::Moore { radius } => square(*radius, &|_, _| true),
+            Neighborhood::VonNeumann { radius } => square(*radius, &|x, y| {
+                x.unsigned_abs() + y.unsigned_abs() <= *radius
+            }),
+            Neighborhood::Hexagonal => [(-1, -1), (0, -1), (-1, 0), (1, 0), (0, 1), (1, 1)]
+                .into_iter()
+                .map(|(x, y)| NeighborOffset { x, y, weight: 1 })
+                .collect(),
+            Neighborhood::Custom(offsets) => offsets.clone(),
+        }
+    }
+
+    /**
+     * The largest neighbor count this neighborhood can produce, or `None` when the weights add up
+     * to more than a `u32` holds.
+     */
+    pub fn checked_max_count(&self) -> Option<u32> {
+        self.offsets()
+            .iter()
+            .try_fold(0u32, |sum, offset| sum.checked_add(offset.weight))
+    }
+
+    /**
+     * The largest neighbor count this neighborhood can produce, at most `u32::MAX`. `Rule::check`
+     * rejects neighborhoods whose count doesn't fit.
+     */
+    pub fn max_count(&self) -> u32 {
+        self.checked_max_count().unwrap_or(u32::MAX)
+    }
+
+    /**
+     * Generate the wgsl that adds up the neighbors of the cell at `location` into `result`.
+     * Square neighborhoods become loops so large radii stay small in the shader, the others are
+     * unrolled.
+     */
+    pub fn to_shader(&self) -> String {
+        let square_loop = |radius: u32, condition: &str| {
+            format!(
+                "for (var y: i32 = -{radius}; y <= {radius}; y++) {{ \
+                 for (var x: i32 = -{radius}; x <= {radius}; x++) {{ \
+                 if {condition} {{ continue; }} \
+                 result += is_alive(location, x, y); }} }}"
+            )
+        };
+
+        match self {
+            Neighborhood::Moore { radius } => square_loop(*radius, "x == 0 && y == 0"),
+            Neighborhood::VonNeumann { radius } => square_loop(
+                *radius,
+                &format!("(x == 0 && y == 0) || abs(x) + abs(y) > {radius}"),
+            ),
+            _ => self
+                .offsets()
+                .iter()
+                .map(|NeighborOffset { x, y, weight }| match weight {
+                    1 => format!("result += is_alive(location, {x}, {y});"),
+                    _ => format!("result += {weight}u * is_alive(location, {x}, {y});"),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Grid;
+
+    fn positions(neighborhood: &Neighborhood) -> Vec<(i32, i32)> {
+        let mut positions = neighborhood
+            .offsets()
+            .iter()
+            .map(|offset| (offset.x, offset.y))
+            .collect::<Vec<_>>();
+        positions.sort_unstable();
+        positions
+    }
+
+    #[test]
+    fn square_neighborhoods() {
+        let sizes = [
+            (Neighborhood::Moore { radius: 1 }, 8),
+            (Neighborhood::Moore { radius: 2 }, 24),
+            (Neighborhood::VonNeumann { radius: 1 }, 4),
+            (Neighborhood::VonNeumann { radius: 2 }, 12),
+        ];
+        for (neighborhood, size) in sizes {
+            assert_eq!(neighborhood.offsets().len(), size, "{neighborhood:?}");
+            assert_eq!(neighborhood.max_count(), size as u32, "{neighborhood:?}");
+            assert!(!positions(&neighborhood).contains(&(0, 0)));
+        }
+        assert_eq!(
+            positions(&Neighborhood::VonNeumann { radius: 1 }),
+            [(-1, 0), (0, -1), (0, 1), (1, 0)]
+        );
+    }
+
+    #[test]
+    fn hexagonal_neighborhood_matches_golly() {
+        // Golly skews the hexagonal grid so the top right and bottom left cells are left out:
+        //   a b .
+        //   c X d
+        //   . e f
+        let golly = positions(&Neighborhood::Moore { radius: 1 })
+            .into_iter()
+            .filter(|&offset| offset != (1, -1) && offset != (-1, 1))
+            .collect::<Vec<_>>();
+        assert_eq!(positions(&Neighborhood::Hexagonal), golly);
+        assert_eq!(Neighborhood::Hexagonal.max_count(), 6);
+    }
+
+    #[test]
+    fn custom_neighborhoods_are_weighted() {
+        let neighborhood = Neighborhood::Custom(vec![
+            NeighborOffset {
+                x: 0,
+                y: -1,
+                weight: 2,
+            },
+            NeighborOffset {
+                x: 1,
+                y: 0,
+                weight: 1,
+            },
+            NeighborOffset {
+                x: -2,
+                y: 0,
+                weight: 5,
+            },
+        ]);
+        assert_eq!(neighborhood.max_count(), 8);
+        assert_eq!(
+            neighborhood.to_shader(),
+            "result += 2u * is_alive(location, 0, -1); result += is_alive(location, 1, 0); \
+             result += 5u * is_alive(location, -2, 0);"
+        );
+
+        let mut grid = Grid::new(5, 5);
+        grid.set(2, 1, 1);
+        grid.set(0, 2, 1);
+        // A dying cell doesn't count
+        grid.set(3, 2, 2);
+        assert_eq!(grid.count_neighbors(2, 2, &neighborhood), 7);
+    }
+
+    #[test]
+    fn counts_that_overflow_saturate() {
+        let neighborhood = Neighborhood::Custom(vec![
+            NeighborOffset {
+                x: 0,
+                y: -1,
+                weight: u32::MAX,
+            },
+            NeighborOffset {
+                x: 0,
+                y: 1,
+                weight: 1,
+            },
+        ]);
+        assert_eq!(neighborhood.checked_max_count(), None);
+        assert_eq!(neighborhood.max_count(), u32::MAX);
+        assert_eq!(Neighborhood::default().checked_max_count(), Some(8));
+    }
+}
@@ -0,0 +1,590 @@
+use super::{exprs::*, Expr, Rule, Statement};
+
+/**
+ * An inclusive range of values `(min, max)` an expression can take.
+ */
+type Range = (u32, u32);
+
+const ANY: Range = (0, u32::MAX);
+
+/**
+ * The values the inputs of a rule can take. The optimizer only keeps the behaviour of a rule the
+ * same for inputs within these bounds, which lets it drop tests that can never be true, such as
+ * `n == 9` in the 3x3 Moore neighborhood.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub state: Range,
+    pub neighbors: Range,
+    pub neighbor_mask: Range,
+}
+
+impl Bounds {
+    /**
+     * Every state of the rule, every neighbor count its neighborhood can produce and every mask of
+     * the 8 surrounding cells if the rule reads it.
+     */
+    pub fn of(rule: &Rule) -> Self {
+        Self {
+            state: (0, rule.states.saturating_sub(1)),
+            neighbors: (0, rule.neighborhood.max_count()),
+            neighbor_mask: match rule.statement.reads(&Expr::NeighborMask) {
+                true => (0, 255),
+                false => (0, 0),
+            },
+        }
+    }
+
+    /**
+     * The bounds inside a branch that is only taken when `condition` is `truth`. Conditions that
+     * only read one input narrow that input to the smallest range of values the condition allows,
+     * the others don't narrow anything.
+     */
+    fn assuming(&self, condition: &Expr, truth: bool) -> Self {
+        use Expr::*;
+
+        let holds = |state, neighbors, neighbor_mask| {
+            (condition.eval_cell(state, neighbors, neighbor_mask) != 0) == truth
+        };
+        let narrow = |(mut min, mut max): Range, holds: &dyn Fn(u32) -> bool| {
+            while min < max && !holds(min) {
+                min += 1;
+            }
+            while max > min && !holds(max) {
+                max -= 1;
+            }
+            (min, max)
+        };
+
+        let mut bounds = *self;
+        let inputs = [State, Neighbors, NeighborMask];
+        let read = inputs
+            .iter()
+            .filter(|input| condition.reads(input))
+            .collect::<Vec<_>>();
+        match read[..] {
+            [] | [State] => bounds.state = narrow(self.state, &|state| holds(state, 0, 0)),
+            [Neighbors] => {
+                bounds.neighbors = narrow(self.neighbors, &|neighbors| holds(0, neighbors, 0))
+            }
+            [NeighborMask] => {
+                bounds.neighbor_mask = narrow(self.neighbor_mask, &|mask| holds(0, 0, mask))
+            }
+            _ => {}
+        }
+        bounds
+    }
+}
+
+impl Expr {
+    /**
+     * Simplify this expression for inputs within `bounds`. Constants are folded, operations that
+     * can't change their operand are removed, comparisons with a known outcome become constants
+     * and chains of equality tests like `n == 2 || n == 3 || n == 6` become a single range test
+     * or a lookup in a bitmask indexed by the neighbor count.
+     */
+    pub fn optimize(&self, bounds: &Bounds) -> Expr {
+        self.optimize_in(bounds, &mut Vec::new())
+    }
+
+    fn optimize_in(&self, bounds: &Bounds, scope: &mut Vec<(String, Range)>) -> Expr {
+        use Expr::*;
+
+        if let Some((subject, values)) = self.equalities() {
+            let range = subject.range(bounds, scope);
+            return membership(subject, values, range);
+        }
+
+        let expr = match self {
+            Let { name, value, body } => {
+                let value = value.optimize_in(bounds, scope);
+                let range = value.range(bounds, scope);
+                scope.push((name.clone(), range));
+                let body = body.optimize_in(bounds, scope);
+                scope.pop();
+
+                if body.reads(&Var(name.clone())) {
+                    let_in(name, value, body)
+                } else {
+                    body
+                }
+            }
+            _ => {
+                let operands = self
+                    .operands()
+                    .iter()
+                    .map(|operand| operand.optimize_in(bounds, scope))
+                    .collect();
+                self.with_operands(operands).simplify(bounds, scope)
+            }
+        };
+
+        // Constant variables are substituted here too, their range is a single value
+        match expr.range(bounds, scope) {
+            (min, max) if min == max => U32(min),
+            _ => expr,
+        }
+    }
+
+    /**
+     * Remove operations that don't change their operand, the operands are already optimized.
+     */
+    fn simplify(self, bounds: &Bounds, scope: &mut Vec<(String, Range)>) -> Expr {
+        use Expr::*;
+
+        let operands = self.operands();
+        if !operands.is_empty() && operands.iter().all(|operand| matches!(operand, U32(_))) {
+            return U32(self.eval_state(0, 0));
+        }
+
+        let mut range = |expr: &Expr| expr.range(bounds, scope);
+        match self {
+            Add(lhs, rhs) if *rhs == U32(0) => *lhs,
+            Add(lhs, rhs) if *lhs == U32(0) => *rhs,
+            Sub(lhs, rhs) if *rhs == U32(0) => *lhs,
+            Mul(lhs, rhs) if *rhs == U32(1) => *lhs,
+            Mul(lhs, rhs) if *lhs == U32(1) => *rhs,
+            Mod(lhs, rhs) if range(&lhs).1 < range(&rhs).0 => *lhs,
+            Min(lhs, rhs) if range(&lhs).1 <= range(&rhs).0 => *lhs,
+            Min(lhs, rhs) if range(&rhs).1 <= range(&lhs).0 => *rhs,
+            Max(lhs, rhs) if range(&lhs).0 >= range(&rhs).1 => *lhs,
+            Max(lhs, rhs) if range(&rhs).0 >= range(&lhs).1 => *rhs,
+            Or(lhs, rhs) if *rhs == U32(0) => *lhs,
+            Or(lhs, rhs) if *lhs == U32(0) => *rhs,
+            // Anding a boolean with an odd number keeps its lowest bit, the boolean itself
+            And(lhs, rhs) if range(&lhs).1 <= 1 && matches!(*rhs, U32(mask) if mask & 1 == 1) => {
+                *lhs
+            }
+            And(lhs, rhs) if range(&rhs).1 <= 1 && matches!(*lhs, U32(mask) if mask & 1 == 1) => {
+                *rhs
+            }
+            Not(expr) => match *expr {
+                Not(inner) if range(&inner).1 <= 1 => *inner,
+                expr => not(expr),
+            },
+            expr => expr,
+        }
+    }
+
+    /**
+     * The range of values this expression can take for inputs within `bounds`. Ranges are exact
+     * for constants and conservative for everything else.
+     */
+    fn range(&self, bounds: &Bounds, scope: &mut Vec<(String, Range)>) -> Range {
+        use Expr::*;
+
+        let truth = |always: bool, never: bool| match (always, never) {
+            (true, _) => (1, 1),
+            (_, true) => (0, 0),
+            _ => (0, 1),
+        };
+
+        match self {
+            U32(value) => (*value, *value),
+            Alive => truth(
+                bounds.state == (1, 1),
+                bounds.state.0 > 1 || bounds.state.1 < 1,
+            ),
+            State => bounds.state,
+            Neighbors => bounds.neighbors,
+            NeighborMask => bounds.neighbor_mask,
+            Var(name) => match scope.iter().rev().find(|(bound, _)| bound == name) {
+                Some((_, range)) => *range,
+                None => ANY,
+            },
+            Let { name, value, body } => {
+                let value = value.range(bounds, scope);
+                scope.push((name.clone(), value));
+                let body = body.range(bounds, scope);
+                scope.pop();
+                body
+            }
+            _ => {
+                let operands = self
+                    .operands()
+                    .iter()
+                    .map(|operand| operand.range(bounds, scope))
+                    .collect::<Vec<_>>();
+                let (a, b) = (operands[0], operands.get(1).copied().unwrap_or_default());
+
+                match self {
+                    Not(_) => truth(a == (0, 0), a.0 > 0),
+                    Add(..) => match a.1.checked_add(b.1) {
+                        Some(max) => (a.0 + b.0, max),
+                        None => ANY,
+                    },
+                    // Subtraction wraps around when the result could be negative
+                    Sub(..) if a.0 >= b.1 => (a.0 - b.1, a.1 - b.0),
+                    Sub(..) => ANY,
+                    Mul(..) => match a.1.checked_mul(b.1) {
+                        Some(max) => (a.0 * b.0, max),
+                        None => ANY,
+                    },
+                    Mod(..) if a.1 < b.0 => a,
+                    Mod(..) if b.0 > 0 => (0, a.1.min(b.1 - 1)),
+                    Mod(..) => (0, a.1),
+                    Min(..) => (a.0.min(b.0), a.1.min(b.1)),
+                    Max(..) => (a.0.max(b.0), a.1.max(b.1)),
+                    Shr(..) if b.1 < 32 => (a.0 >> b.1, a.1 >> b.0),
+                    Shr(..) => (0, a.1),
+                    And(..) => (0, a.1.min(b.1)),
+                    Or(..) => match a.1 | b.1 {
+                        0 => (0, 0),
+                        bits => (a.0.max(b.0), u32::MAX >> bits.leading_zeros()),
+                    },
+                    Gt(..) => truth(a.0 > b.1, a.1 <= b.0),
+                    Gte(..) => truth(a.0 >= b.1, a.1 < b.0),
+                    Lt(..) => truth(a.1 < b.0, a.0 >= b.1),
+                    Lte(..) => truth(a.1 <= b.0, a.0 > b.1),
+                    Equal(..) => truth(a.0 == a.1 && a == b, a.1 < b.0 || b.1 < a.0),
+                    U32(_) | Alive | State | Neighbors | NeighborMask | Var(_) | Let { .. } => {
+                        unreachable!("handled above")
+                    }
+                }
+            }
+        }
+    }
+
+    /**
+     * Split `subject == a || subject == b || ...` into the subject and the values it is compared
+     * to, where the subject is the state, the neighbor count or the neighbor mask.
+     */
+    fn equalities(&self) -> Option<(Expr, Vec<u32>)> {
+        use Expr::*;
+
+        match self {
+            Equal(lhs, rhs) => match (&**lhs, &**rhs) {
+                (subject @ (State | Neighbors | NeighborMask), U32(value))
+                | (U32(value), subject @ (State | Neighbors | NeighborMask)) => {
+                    Some((subject.clone(), vec![*value]))
+                }
+                _ => None,
+            },
+            Or(lhs, rhs) => {
+                let (subject, mut values) = lhs.equalities()?;
+                let (other, more) = rhs.equalities()?;
+                if subject != other {
+                    return None;
+                }
+                values.extend(more);
+                Some((subject, values))
+            }
+            _ => None,
+        }
+    }
+
+    /**
+     * Whether the value of this expression depends on `input`, which is `State`, `Neighbors`,
+     * `NeighborMask` or a variable. Expressions that read variables are assumed to depend on
+     * every input.
+     */
+    pub(super) fn reads(&self, input: &Expr) -> bool {
+        use Expr::*;
+
+        match self {
+            Alive => *input == State,
+            State | Neighbors | NeighborMask => self == input,
+            Var(_) => matches!(input, State | Neighbors | NeighborMask) || input == self,
+            Let { name, value, body } => {
+                value.reads(input) || (*input != Var(name.clone()) && body.reads(input))
+            }
+            _ => self.operands().iter().any(|operand| operand.reads(input)),
+        }
+    }
+
+    /**
+     * Rebuild an expression that does not bind a variable with new operands.
+     */
+    fn with_operands(&self, operands: Vec<Expr>) -> Expr {
+        use Expr::*;
+
+        let mut operands = operands.into_iter().map(Box::new);
+        let mut next = || operands.next().unwrap();
+        match self {
+            Not(_) => Not(next()),
+            Add(..) => Add(next(), next()),
+            Sub(..) => Sub(next(), next()),
+            Mul(..) => Mul(next(), next()),
+            Mod(..) => Mod(next(), next()),
+            Min(..) => Min(next(), next()),
+            Max(..) => Max(next(), next()),
+            Shr(..) => Shr(next(), next()),
+            Gt(..) => Gt(next(), next()),
+            Gte(..) => Gte(next(), next()),
+            Lt(..) => Lt(next(), next()),
+            Lte(..) => Lte(next(), next()),
+            And(..) => And(next(), next()),
+            Or(..) => Or(next(), next()),
+            Equal(..) => Equal(next(), next()),
+            U32(_) | Alive | State | Neighbors | NeighborMask | Var(_) | Let { .. } => self.clone(),
+        }
+    }
+}
+
+/**
+ * An expression that is 1 when `subject`, which lies in `range`, is one of `values`. A single
+ * contiguous run of values becomes a range test, other sets of small values become a bitmask.
+ */
+fn membership(subject: Expr, mut values: Vec<u32>, (min, max): Range) -> Expr {
+    values.retain(|value| (min..=max).contains(value));
+    values.sort_unstable();
+    values.dedup();
+
+    match values[..] {
+        [] => const_u32(0),
+        _ if values.len() as u64 == (max - min) as u64 + 1 => const_u32(1),
+        [value] => equal(subject, const_u32(value)),
+        [first, .., last] if (last - first) as usize + 1 == values.len() => {
+            if first == min {
+                lte(subject, const_u32(last))
+            } else if last == max {
+                gte(subject, const_u32(first))
+            } else {
+                // Values below `first` wrap around to large numbers, so one comparison is enough
+                lte(sub(subject, const_u32(first)), const_u32(last - first))
+            }
+        }
+        _ if max < 32 => {
+            let mask = values.iter().fold(0, |mask, value| mask | 1 << value);
+            and(shr(const_u32(mask), subject), const_u32(1))
+        }
+        _ => values
+            .into_iter()
+            .map(|value| equal(subject.clone(), const_u32(value)))
+            .reduce(or)
+            .unwrap(),
+    }
+}
+
+impl Statement {
+    /**
+     * Simplify every expression in this statement for inputs within `bounds` and remove branches
+     * that can't be taken or that do the same thing either way.
+     */
+    pub fn optimize(&self, bounds: &Bounds) -> Statement {
+        use Statement::*;
+
+        match self {
+            Void => Void,
+            SetResult(expr) => SetResult(expr.optimize(bounds)),
+            IfThenElse {
+                condition,
+                if_true_then,
+                if_false_then,
+            } => {
+                let condition = condition.optimize(bounds);
+                match condition {
+                    Expr::U32(0) => return if_false_then.optimize(bounds),
+                    Expr::U32(_) => return if_true_then.optimize(bounds),
+                    _ => {}
+                }
+
+                let if_true_then = if_true_then.optimize(&bounds.assuming(&condition, true));
+                let if_false_then = if_false_then.optimize(&bounds.assuming(&condition, false));
+                if if_true_then == if_false_then {
+                    return if_true_then;
+                }
+
+                IfThenElse {
+                    condition,
+                    if_true_then: Box::new(if_true_then),
+                    if_false_then: Box::new(if_false_then),
+                }
+            }
+        }
+    }
+
+    /**
+     * Whether any expression in this statement depends on `input`, see `Expr::reads`.
+     */
+    pub(super) fn reads(&self, input: &Expr) -> bool {
+        use Statement::*;
+
+        match self {
+            Void => false,
+            SetResult(expr) => expr.reads(input),
+            IfThenElse {
+                condition,
+                if_true_then,
+                if_false_then,
+            } => condition.reads(input) || if_true_then.reads(input) || if_false_then.reads(input),
+        }
+    }
+}
+
+impl Rule {
+    /**
+     * A copy of this rule with its statement optimized for the states of the rule and the neighbor
+     * counts of its neighborhood. The rule should pass `check` first.
+     */
+    pub fn optimize(&self) -> Rule {
+        Rule {
+            statement: self.statement.optimize(&Bounds::of(self)),
+            ..self.clone()
+        }
+    }
+
+    /**
+     * Whether the statement depends on the mask of the living neighbors, the shader only builds
+     * the mask for rules that do.
+     */
+    pub fn reads_neighbor_mask(&self) -> bool {
+        self.statement.reads(&Expr::NeighborMask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::{rulesets, statements::*, Neighborhood};
+
+    /**
+     * Assert that the optimized rule gives the same result as the original for every input within
+     * the bounds of the rule, and for every living state and neighbor count of the 3x3 Moore
+     * neighborhood.
+     */
+    fn assert_equivalent(name: &str, rule: &Rule) -> Rule {
+        let optimized = rule.optimize();
+        let bounds = Bounds::of(rule);
+        for state in bounds.state.0..=bounds.state.1 {
+            for neighbors in bounds.neighbors.0..=bounds.neighbors.1 {
+                for mask in bounds.neighbor_mask.0..=bounds.neighbor_mask.1 {
+                    assert_eq!(
+                        optimized.statement.eval_cell(state, neighbors, mask),
+                        rule.statement.eval_cell(state, neighbors, mask),
+                        "{name} with state {state}, {neighbors} neighbors and mask {mask:#010b}"
+                    );
+                }
+            }
+        }
+        for alive in [false, true] {
+            for neighbors in 0..=8 {
+                assert_eq!(
+                    optimized.statement.eval(alive, neighbors),
+                    rule.statement.eval(alive, neighbors),
+                    "{name} alive: {alive} with {neighbors} neighbors"
+                );
+            }
+        }
+        optimized
+    }
+
+    #[test]
+    fn catalog_rules_keep_their_behaviour() {
+        for (name, _) in rulesets::CATALOG {
+            assert_equivalent(name, &rulesets::by_name(name).unwrap());
+        }
+    }
+
+    #[test]
+    fn constants_fold() {
+        let rule = Rule::new(set_result(add(
+            const_u32(2),
+            mul(const_u32(3), const_u32(4)),
+        )))
+        .with_states(16);
+        let optimized = assert_equivalent("arithmetic", &rule);
+        assert_eq!(optimized.statement, set_result(const_u32(14)));
+
+        let rule = Rule::new(set_result(let_in(
+            "a",
+            const_u32(3),
+            equal(neighbors(), var("a")),
+        )));
+        let optimized = assert_equivalent("constant variable", &rule);
+        assert_eq!(
+            optimized.statement,
+            set_result(equal(neighbors(), const_u32(3)))
+        );
+
+        let rule = Rule::new(set_result(add(
+            mul(neighbors(), const_u32(1)),
+            const_u32(0),
+        )));
+        let optimized = assert_equivalent("identities", &rule);
+        assert_eq!(optimized.statement, set_result(neighbors()));
+    }
+
+    #[test]
+    fn dead_branches_are_removed() {
+        // The 3x3 Moore neighborhood never has 9 neighbors
+        let rule = Rule::new(if_then_else(
+            equal(neighbors(), const_u32(9)),
+            set_result(const_u32(1)),
+            set_result(alive()),
+        ));
+        let optimized = assert_equivalent("impossible count", &rule);
+        assert_eq!(optimized.statement, set_result(alive()));
+
+        // Inside `if alive` the cell is known to be alive
+        let rule = Rule::new(if_then_else(
+            alive(),
+            if_then_else(
+                alive(),
+                set_result(gt(neighbors(), const_u32(1))),
+                set_result(const_u32(0)),
+            ),
+            set_result(equal(neighbors(), const_u32(3))),
+        ));
+        let optimized = assert_equivalent("nested condition", &rule);
+        assert_eq!(
+            optimized.statement,
+            if_then_else(
+                alive(),
+                set_result(gt(neighbors(), const_u32(1))),
+                set_result(equal(neighbors(), const_u32(3)))
+            )
+        );
+
+        // Both branches do the same thing
+        let rule = Rule::new(if_then_else(
+            lt(neighbors(), const_u32(4)),
+            set_result(const_u32(1)),
+            set_result(const_u32(1)),
+        ));
+        let optimized = assert_equivalent("equal branches", &rule);
+        assert_eq!(optimized.statement, set_result(const_u32(1)));
+    }
+
+    #[test]
+    fn equality_chains_become_ranges_and_masks() {
+        let chain = |values: &[u32]| {
+            values
+                .iter()
+                .map(|value| equal(neighbors(), const_u32(*value)))
+                .reduce(or)
+                .unwrap()
+        };
+
+        let rule = Rule::new(set_result(chain(&[2, 3, 4])));
+        let optimized = assert_equivalent("run", &rule);
+        assert_eq!(
+            optimized.statement,
+            set_result(lte(sub(neighbors(), const_u32(2)), const_u32(2)))
+        );
+
+        let rule = Rule::new(set_result(chain(&[6, 7, 8, 7])));
+        let optimized = assert_equivalent("run to the end", &rule);
+        assert_eq!(
+            optimized.statement,
+            set_result(gte(neighbors(), const_u32(6)))
+        );
+
+        let rule = Rule::new(set_result(chain(&[1, 3, 6])));
+        let optimized = assert_equivalent("scattered", &rule);
+        assert_eq!(
+            optimized.statement,
+            set_result(and(shr(const_u32(0b1001010), neighbors()), const_u32(1)))
+        );
+
+        let rule = Rule::new(set_result(chain(&[0, 1, 2, 3, 4, 5, 6, 7, 8])));
+        let optimized = assert_equivalent("every count", &rule);
+        assert_eq!(optimized.statement, set_result(const_u32(1)));
+
+        // Too many neighbors for a 32 bit mask
+        let rule = Rule::new(set_result(chain(&[1, 40])))
+            .with_neighborhood(Neighborhood::Moore { radius: 3 });
+        let optimized = assert_equivalent("large counts", &rule);
+        assert_eq!(optimized.statement, rule.statement);
+    }
+}
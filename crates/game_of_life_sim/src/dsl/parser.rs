@@ -0,0 +1,809 @@
+use std::fmt;
+
+use super::{check::Type, exprs, statements, Expr, NeighborOffset, Neighborhood, Rule, Statement};
+
+/**
+ * An error produced while lexing or parsing the textual form of the DSL. Line and column are
+ * 1-based and point at the first character of the offending token.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/**
+ * Parse a complete rule written in the textual syntax, e.g.
+ *
+ * ```text
+ * if alive { result = n == 2 || n == 3 } else { result = n == 3 }
+ * ```
+ */
+pub fn parse(source: &str) -> Result<Statement, ParseError> {
+    let mut parser = Parser::new(source)?;
+    let statement = parser.statement()?;
+    parser.expect(&Token::Eof)?;
+    Ok(statement)
+}
+
+/**
+ * Parse a rule, a statement optionally preceded by the neighborhood it counts neighbors in and
+ * the number of states a cell can be in. A two state rule over the 3x3 Moore neighborhood is
+ * used when they are not given.
+ *
+ * ```text
+ * neighborhood von_neumann(2)
+ * states 3
+ * if state == 0 { result = n == 2 } else if state == 1 { result = 2 } else { result = 0 }
+ * ```
+ *
+ * Supported neighborhoods are `moore(radius)`, `von_neumann(radius)`, `hexagonal` and
+ * `custom [(x, y), (x, y, weight), ...]`.
+ */
+pub fn parse_rule(source: &str) -> Result<Rule, ParseError> {
+    let mut parser = Parser::new(source)?;
+    let mut rule = Rule::new(statements::void());
+    loop {
+        if parser.is_keyword("neighborhood") {
+            parser.next();
+            rule.neighborhood = parser.neighborhood()?;
+        } else if parser.is_keyword("states") {
+            let token = parser.next();
+            rule.states = parser.number()?;
+            if rule.states < 2 {
+                return Err(parser.error_at(&token, "a rule needs at least 2 states".to_string()));
+            }
+        } else {
+            break;
+        }
+    }
+    rule.statement = parser.statement()?;
+    parser.expect(&Token::Eof)?;
+    Ok(rule)
+}
+
+/**
+ * Parse a single expression written in the textual syntax, e.g. `n == 2 || n == 3`.
+ */
+pub fn parse_expr(source: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser::new(source)?;
+    let expr = parser.expr()?;
+    parser.expect(&Token::Eof)?;
+    Ok(expr)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Number(u32),
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Assign,
+    Semicolon,
+    Comma,
+    Plus,
+    Minus,
+    Star,
+    Percent,
+    Bang,
+    EqEq,
+    NotEq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Shr,
+    Amp,
+    Pipe,
+    AndAnd,
+    OrOr,
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "`{name}`"),
+            Token::Number(value) => write!(f, "`{value}`"),
+            Token::LBrace => write!(f, "`{{`"),
+            Token::RBrace => write!(f, "`}}`"),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::LBracket => write!(f, "`[`"),
+            Token::RBracket => write!(f, "`]`"),
+            Token::Assign => write!(f, "`=`"),
+            Token::Semicolon => write!(f, "`;`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Plus => write!(f, "`+`"),
+            Token::Minus => write!(f, "`-`"),
+            Token::Star => write!(f, "`*`"),
+            Token::Percent => write!(f, "`%`"),
+            Token::Bang => write!(f, "`!`"),
+            Token::EqEq => write!(f, "`==`"),
+            Token::NotEq => write!(f, "`!=`"),
+            Token::Lt => write!(f, "`<`"),
+            Token::Lte => write!(f, "`<=`"),
+            Token::Gt => write!(f, "`>`"),
+            Token::Gte => write!(f, "`>=`"),
+            Token::Shr => write!(f, "`>>`"),
+            Token::Amp => write!(f, "`&`"),
+            Token::Pipe => write!(f, "`|`"),
+            Token::AndAnd => write!(f, "`&&`"),
+            Token::OrOr => write!(f, "`||`"),
+            Token::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Spanned {
+    token: Token,
+    line: usize,
+    column: usize,
+}
+
+fn lex(source: &str) -> Result<Vec<Spanned>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+
+    while let Some(&c) = chars.peek() {
+        let (start_line, start_column) = (line, column);
+        let error = |message: String| ParseError {
+            message,
+            line: start_line,
+            column: start_column,
+        };
+
+        if c == '\n' {
+            chars.next();
+            line += 1;
+            column = 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            chars.next();
+            column += 1;
+            continue;
+        }
+
+        chars.next();
+        column += 1;
+
+        let token = match c {
+            '/' if chars.peek() == Some(&'/') => {
+                // Line comment, skip until the end of the line
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+                continue;
+            }
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            ';' => Token::Semicolon,
+            ',' => Token::Comma,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '%' => Token::Percent,
+            '=' | '!' | '<' | '>' | '&' | '|' => {
+                let next = chars.peek().copied();
+                let (token, consumed) = match (c, next) {
+                    ('=', Some('=')) => (Token::EqEq, true),
+                    ('=', _) => (Token::Assign, false),
+                    ('!', Some('=')) => (Token::NotEq, true),
+                    ('!', _) => (Token::Bang, false),
+                    ('<', Some('=')) => (Token::Lte, true),
+                    ('<', _) => (Token::Lt, false),
+                    ('>', Some('=')) => (Token::Gte, true),
+                    ('>', Some('>')) => (Token::Shr, true),
+                    ('>', _) => (Token::Gt, false),
+                    ('&', Some('&')) => (Token::AndAnd, true),
+                    ('&', _) => (Token::Amp, false),
+                    ('|', Some('|')) => (Token::OrOr, true),
+                    ('|', _) => (Token::Pipe, false),
+                    _ => unreachable!("only the characters above are matched"),
+                };
+                if consumed {
+                    chars.next();
+                    column += 1;
+                }
+                token
+            }
+            c if c.is_ascii_digit() => {
+                let mut digits = c.to_string();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(d);
+                    chars.next();
+                    column += 1;
+                }
+                let value = digits
+                    .parse()
+                    .map_err(|_| error(format!("integer `{digits}` does not fit in a u32")))?;
+                Token::Number(value)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(&d) = chars
+                    .peek()
+                    .filter(|d| d.is_ascii_alphanumeric() || **d == '_')
+                {
+                    ident.push(d);
+                    chars.next();
+                    column += 1;
+                }
+                Token::Ident(ident)
+            }
+            c => return Err(error(format!("unexpected character `{c}`"))),
+        };
+
+        tokens.push(Spanned {
+            token,
+            line: start_line,
+            column: start_column,
+        });
+    }
+
+    tokens.push(Spanned {
+        token: Token::Eof,
+        line,
+        column,
+    });
+
+    Ok(tokens)
+}
+
+/**
+ * Identifiers with a meaning in the language, these cannot be used as names in a `let`.
+ */
+const KEYWORDS: &[&str] = &[
+    "if",
+    "else",
+    "result",
+    "let",
+    "in",
+    "alive",
+    "state",
+    "neighbors",
+    "n",
+    "neighbor_mask",
+    "true",
+    "false",
+    "min",
+    "max",
+];
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    position: usize,
+    /// Names bound by the `let` expressions enclosing the current position and their types
+    scope: Vec<(String, Type)>,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self, ParseError> {
+        Ok(Self {
+            tokens: lex(source)?,
+            position: 0,
+            scope: Vec::new(),
+        })
+    }
+
+    fn peek(&self) -> &Spanned {
+        &self.tokens[self.position]
+    }
+
+    fn next(&mut self) -> Spanned {
+        let token = self.tokens[self.position].clone();
+        if token.token != Token::Eof {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error_at(&self, token: &Spanned, message: String) -> ParseError {
+        ParseError {
+            message,
+            line: token.line,
+            column: token.column,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().token, Token::Ident(name) if name == keyword)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if &self.peek().token == token {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), ParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            let found = self.peek().clone();
+            Err(self.error_at(&found, format!("expected {token}, found {}", found.token)))
+        }
+    }
+
+    fn number(&mut self) -> Result<u32, ParseError> {
+        let token = self.next();
+        match &token.token {
+            Token::Number(value) => Ok(*value),
+            found => Err(self.error_at(&token, format!("expected a number, found {found}"))),
+        }
+    }
+
+    fn signed_number(&mut self) -> Result<i32, ParseError> {
+        let negative = self.eat(&Token::Minus);
+        let token = self.peek().clone();
+        let value = self.number()? as i64;
+        let value = if negative { -value } else { value };
+        i32::try_from(value)
+            .map_err(|_| self.error_at(&token, format!("offset {value} is out of range")))
+    }
+
+    /**
+     * neighborhood := 'moore' ('(' number ')')?
+     *               | 'von_neumann' ('(' number ')')?
+     *               | 'hexagonal'
+     *               | 'custom' '[' (offset (',' offset)* ','?)? ']'
+     * offset       := '(' signed ',' signed (',' number)? ')'
+     */
+    fn neighborhood(&mut self) -> Result<Neighborhood, ParseError> {
+        let token = self.next();
+        let Token::Ident(name) = &token.token else {
+            return Err(self.error_at(
+                &token,
+                format!("expected a neighborhood, found {}", token.token),
+            ));
+        };
+
+        let radius = |parser: &mut Self| -> Result<u32, ParseError> {
+            if parser.eat(&Token::LParen) {
+                let radius = parser.number()?;
+                parser.expect(&Token::RParen)?;
+                Ok(radius)
+            } else {
+                Ok(1)
+            }
+        };
+
+        match name.as_str() {
+            "moore" => Ok(Neighborhood::Moore {
+                radius: radius(self)?,
+            }),
+            "von_neumann" => Ok(Neighborhood::VonNeumann {
+                radius: radius(self)?,
+            }),
+            "hexagonal" => Ok(Neighborhood::Hexagonal),
+            "custom" => {
+                self.expect(&Token::LBracket)?;
+                let mut offsets = Vec::new();
+                while !self.eat(&Token::RBracket) {
+                    self.expect(&Token::LParen)?;
+                    let x = self.signed_number()?;
+                    self.expect(&Token::Comma)?;
+                    let y = self.signed_number()?;
+                    let weight = if self.eat(&Token::Comma) {
+                        self.number()?
+                    } else {
+                        1
+                    };
+                    self.expect(&Token::RParen)?;
+                    offsets.push(NeighborOffset { x, y, weight });
+
+                    if !self.eat(&Token::Comma) {
+                        self.expect(&Token::RBracket)?;
+                        break;
+                    }
+                }
+                Ok(Neighborhood::Custom(offsets))
+            }
+            _ => Err(self.error_at(&token, format!("unknown neighborhood `{name}`"))),
+        }
+    }
+
+    /**
+     * statement := 'if' expr block ('else' (block | statement))?
+     *            | 'result' '=' expr ';'?
+     *            | block
+     */
+    fn statement(&mut self) -> Result<Statement, ParseError> {
+        if self.is_keyword("if") {
+            self.next();
+            let condition = self.expr()?;
+            let if_true_then = self.block()?;
+            let if_false_then = if self.is_keyword("else") {
+                self.next();
+                if self.is_keyword("if") {
+                    self.statement()?
+                } else {
+                    self.block()?
+                }
+            } else {
+                statements::void()
+            };
+            Ok(statements::if_then_else(
+                condition,
+                if_true_then,
+                if_false_then,
+            ))
+        } else if self.is_keyword("result") {
+            self.next();
+            self.expect(&Token::Assign)?;
+            let expr = self.expr()?;
+            self.eat(&Token::Semicolon);
+            Ok(statements::set_result(expr))
+        } else if self.peek().token == Token::LBrace {
+            self.block()
+        } else {
+            let found = self.peek().clone();
+            Err(self.error_at(
+                &found,
+                format!("expected `if`, `result` or `{{`, found {}", found.token),
+            ))
+        }
+    }
+
+    /**
+     * block := '{' statement? '}'
+     */
+    fn block(&mut self) -> Result<Statement, ParseError> {
+        self.expect(&Token::LBrace)?;
+        if self.eat(&Token::RBrace) {
+            return Ok(statements::void());
+        }
+        let statement = self.statement()?;
+        self.expect(&Token::RBrace)?;
+        Ok(statement)
+    }
+
+    /**
+     * expr := 'let' ident '=' expr 'in' expr
+     *       | or_expr
+     */
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        if !self.is_keyword("let") {
+            return self.or_expr();
+        }
+
+        self.next();
+        let token = self.next();
+        let name = match &token.token {
+            Token::Ident(name) if !KEYWORDS.contains(&name.as_str()) => name.clone(),
+            Token::Ident(name) => {
+                return Err(self.error_at(&token, format!("`{name}` is a reserved word")))
+            }
+            found => return Err(self.error_at(&token, format!("expected a name, found {found}"))),
+        };
+        self.expect(&Token::Assign)?;
+        let value = self.expr()?;
+        if !self.is_keyword("in") {
+            let found = self.peek().clone();
+            return Err(self.error_at(&found, format!("expected `in`, found {}", found.token)));
+        }
+        self.next();
+
+        self.scope.push((name.clone(), value.type_in(&self.scope)));
+        let body = self.expr();
+        self.scope.pop();
+
+        Ok(exprs::let_in(&name, value, body?))
+    }
+
+    fn or_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.and_expr()?;
+        while self.eat(&Token::OrOr) {
+            let rhs = self.and_expr()?;
+            lhs = exprs::or(self.truth(lhs), self.truth(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.comparison()?;
+        while self.eat(&Token::AndAnd) {
+            let rhs = self.comparison()?;
+            lhs = exprs::and(self.truth(lhs), self.truth(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /**
+     * `&&` and `||` are logical: an integer operand is true when it is non-zero, so it is compared
+     * with 0 rather than combined bit by bit. `&` and `|` work on the bits of integers.
+     */
+    fn truth(&self, expr: Expr) -> Expr {
+        match expr.type_in(&self.scope) {
+            Type::Bool => expr,
+            Type::U32 => exprs::not_equal(expr, exprs::const_u32(0)),
+        }
+    }
+
+    /**
+     * Comparisons do not chain, `a < b < c` is rejected rather than silently comparing a
+     * boolean against `c`.
+     */
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        let lhs = self.bit_or()?;
+        let op = match self.peek().token {
+            Token::EqEq => exprs::equal,
+            Token::NotEq => exprs::not_equal,
+            Token::Lt => exprs::lt,
+            Token::Lte => exprs::lte,
+            Token::Gt => exprs::gt,
+            Token::Gte => exprs::gte,
+            _ => return Ok(lhs),
+        };
+        self.next();
+        let expr = op(lhs, self.bit_or()?);
+
+        let next = self.peek().clone();
+        if matches!(
+            next.token,
+            Token::EqEq | Token::NotEq | Token::Lt | Token::Lte | Token::Gt | Token::Gte
+        ) {
+            return Err(self.error_at(
+                &next,
+                "comparisons cannot be chained, use parentheses".to_string(),
+            ));
+        }
+        Ok(expr)
+    }
+
+    fn bit_or(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.bit_and()?;
+        while self.eat(&Token::Pipe) {
+            lhs = exprs::or(lhs, self.bit_and()?);
+        }
+        Ok(lhs)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.shift()?;
+        while self.eat(&Token::Amp) {
+            lhs = exprs::and(lhs, self.shift()?);
+        }
+        Ok(lhs)
+    }
+
+    fn shift(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.additive()?;
+        while self.eat(&Token::Shr) {
+            lhs = exprs::shr(lhs, self.additive()?);
+        }
+        Ok(lhs)
+    }
+
+    fn additive(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.multiplicative()?;
+        loop {
+            let op = match self.peek().token {
+                Token::Plus => exprs::add,
+                Token::Minus => exprs::sub,
+                _ => return Ok(lhs),
+            };
+            self.next();
+            lhs = op(lhs, self.multiplicative()?);
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek().token {
+                Token::Star => exprs::mul,
+                Token::Percent => exprs::modulo,
+                _ => return Ok(lhs),
+            };
+            self.next();
+            lhs = op(lhs, self.unary()?);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.eat(&Token::Bang) {
+            return Ok(exprs::not(self.unary()?));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let token = self.next();
+        match &token.token {
+            Token::Number(value) => Ok(exprs::const_u32(*value)),
+            Token::Ident(name) => match name.as_str() {
+                "alive" => Ok(exprs::alive()),
+                "state" => Ok(exprs::state()),
+                "neighbors" | "n" => Ok(exprs::neighbors()),
+                "neighbor_mask" => Ok(exprs::neighbor_mask()),
+                "true" => Ok(exprs::const_u32(1)),
+                "false" => Ok(exprs::const_u32(0)),
+                "min" | "max" => {
+                    self.expect(&Token::LParen)?;
+                    let lhs = self.expr()?;
+                    self.expect(&Token::Comma)?;
+                    let rhs = self.expr()?;
+                    self.expect(&Token::RParen)?;
+                    Ok(match name.as_str() {
+                        "min" => exprs::min(lhs, rhs),
+                        _ => exprs::max(lhs, rhs),
+                    })
+                }
+                _ if self.scope.iter().any(|(bound, _)| bound == name) => Ok(exprs::var(name)),
+                _ => Err(self.error_at(&token, format!("unknown identifier `{name}`"))),
+            },
+            Token::LParen => {
+                let expr = self.expr()?;
+                self.expect(&Token::RParen)?;
+                Ok(expr)
+            }
+            found => Err(self.error_at(&token, format!("expected an expression, found {found}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::exprs::*;
+
+    fn error(source: &str) -> (usize, usize, String) {
+        let err = parse_rule(source).expect_err("the source is invalid");
+        (err.line, err.column, err.message)
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        assert_eq!(
+            parse_expr("n == 2 || n == 3 && alive").unwrap(),
+            or(
+                equal(neighbors(), const_u32(2)),
+                and(equal(neighbors(), const_u32(3)), alive())
+            )
+        );
+        assert_eq!(
+            parse_expr("1 + 2 * 3 >> 1").unwrap(),
+            shr(
+                add(const_u32(1), mul(const_u32(2), const_u32(3))),
+                const_u32(1)
+            )
+        );
+        assert_eq!(
+            parse_expr("neighbor_mask >> 2 & 1 | 4").unwrap(),
+            or(
+                and(shr(neighbor_mask(), const_u32(2)), const_u32(1)),
+                const_u32(4)
+            )
+        );
+    }
+
+    #[test]
+    fn logical_operators_compare_integers_with_zero() {
+        assert_eq!(
+            parse_expr("n && alive").unwrap(),
+            and(not_equal(neighbors(), const_u32(0)), alive())
+        );
+        assert_eq!(
+            parse_expr("state || false").unwrap(),
+            or(
+                not_equal(state(), const_u32(0)),
+                not_equal(const_u32(0), const_u32(0))
+            )
+        );
+        // A boolean bound by `let` stays a boolean
+        assert_eq!(
+            parse_expr("let a = alive in a && n == 3").unwrap(),
+            let_in(
+                "a",
+                alive(),
+                and(var("a"), equal(neighbors(), const_u32(3)))
+            )
+        );
+
+        // Bit by bit 2 && 1 would be 0
+        let expr = parse_expr("n && alive").unwrap();
+        assert_eq!(expr.eval(true, 2), 1);
+        assert_eq!(expr.eval(true, 0), 0);
+        assert_eq!(parse_expr("n & alive").unwrap().eval(true, 2), 0);
+    }
+
+    #[test]
+    fn rule_headers() {
+        let rule = parse_rule(
+            "neighborhood custom [(-1, 0), (1, 0, 2)]\nstates 3\nresult = state == 0 && n == 2",
+        )
+        .unwrap();
+        assert_eq!(rule.states, 3);
+        assert_eq!(
+            rule.neighborhood,
+            Neighborhood::Custom(vec![
+                NeighborOffset {
+                    x: -1,
+                    y: 0,
+                    weight: 1
+                },
+                NeighborOffset {
+                    x: 1,
+                    y: 0,
+                    weight: 2
+                },
+            ])
+        );
+
+        let rule = parse_rule("if alive { result = 1 } else if n == 3 { result = 1 }").unwrap();
+        assert_eq!(rule.states, 2);
+        assert_eq!(rule.neighborhood, Neighborhood::default());
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        assert_eq!(
+            error("result = n < 2 < 3"),
+            (
+                1,
+                16,
+                "comparisons cannot be chained, use parentheses".to_string()
+            )
+        );
+        assert_eq!(
+            error("if alive {\n    result = count\n}"),
+            (2, 14, "unknown identifier `count`".to_string())
+        );
+        assert_eq!(
+            error("result = n # 2"),
+            (1, 12, "unexpected character `#`".to_string())
+        );
+        assert_eq!(
+            error("result = let n = 2 in n"),
+            (1, 14, "`n` is a reserved word".to_string())
+        );
+        assert_eq!(
+            error("states 1\nresult = 0"),
+            (1, 1, "a rule needs at least 2 states".to_string())
+        );
+        assert_eq!(
+            error("result = (n == 3"),
+            (1, 17, "expected `)`, found end of input".to_string())
+        );
+        assert_eq!(
+            error("result = 4294967296"),
+            (
+                1,
+                10,
+                "integer `4294967296` does not fit in a u32".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn variables_are_scoped_to_the_let_body() {
+        assert_eq!(
+            error("result = (let a = 1 in a) + a"),
+            (1, 29, "unknown identifier `a`".to_string())
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt;
+
+use super::{check::Type, Expr, NeighborOffset, Neighborhood, Rule, Statement};
+
+impl Expr {
+    /**
+     * Whether an `And` or `Or` combines two booleans and is printed as `&&` or `||`. With an
+     * integer operand it works bit by bit and is printed as `&` or `|`.
+     */
+    fn is_logical(&self, scope: &[(String, Type)]) -> bool {
+        self.operands()
+            .iter()
+            .all(|operand| operand.type_in(scope) == Type::Bool)
+    }
+
+    /**
+     * How tightly the outermost operation binds, using the precedence levels of the parser. An
+     * operand with a lower precedence than its position allows is printed in parentheses.
+     */
+    fn precedence(&self, scope: &[(String, Type)]) -> u8 {
+        use Expr::*;
+
+        match self {
+            Let { .. } => 0,
+            Or(..) if self.is_logical(scope) => 1,
+            And(..) if self.is_logical(scope) => 2,
+            Gt(..) | Gte(..) | Lt(..) | Lte(..) | Equal(..) => 3,
+            // Printed as `!=`
+            Not(expr) if matches!(**expr, Equal(..)) => 3,
+            Or(..) => 4,
+            And(..) => 5,
+            Shr(..) => 6,
+            Add(..) | Sub(..) => 7,
+            Mul(..) | Mod(..) => 8,
+            Not(_) => 9,
+            U32(_) | Alive | State | Neighbors | NeighborMask | Var(_) | Min(..) | Max(..) => 10,
+        }
+    }
+
+    fn write(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        min_precedence: u8,
+        scope: &mut Vec<(String, Type)>,
+    ) -> fmt::Result {
+        use Expr::*;
+
+        let precedence = self.precedence(scope);
+        if precedence < min_precedence {
+            write!(f, "(")?;
+            self.write(f, 0, scope)?;
+            return write!(f, ")");
+        }
+
+        // Operators associate to the left and comparisons don't chain, so only the left operand
+        // of an arithmetic or logical operator can have the same precedence without parentheses
+        let binary = |f: &mut fmt::Formatter<'_>,
+                      scope: &mut Vec<(String, Type)>,
+                      op: &str,
+                      lhs: &Expr,
+                      rhs: &Expr| {
+            let lhs_precedence = if precedence == 3 { 4 } else { precedence };
+            lhs.write(f, lhs_precedence, scope)?;
+            write!(f, " {op} ")?;
+            rhs.write(f, precedence + 1, scope)
+        };
+        let call = |f: &mut fmt::Formatter<'_>,
+                    scope: &mut Vec<(String, Type)>,
+                    name: &str,
+                    lhs: &Expr,
+                    rhs: &Expr| {
+            write!(f, "{name}(")?;
+            lhs.write(f, 0, scope)?;
+            write!(f, ", ")?;
+            rhs.write(f, 0, scope)?;
+            write!(f, ")")
+        };
+        let logical = self.is_logical(scope);
+
+        match self {
+            U32(value) => write!(f, "{value}"),
+            Alive => write!(f, "alive"),
+            State => write!(f, "state"),
+            Neighbors => write!(f, "n"),
+            NeighborMask => write!(f, "neighbor_mask"),
+            Var(name) => write!(f, "{name}"),
+            Let { name, value, body } => {
+                write!(f, "let {name} = ")?;
+                value.write(f, 0, scope)?;
+                write!(f, " in ")?;
+                scope.push((name.clone(), value.type_in(scope)));
+                let result = body.write(f, 0, scope);
+                scope.pop();
+                result
+            }
+            Not(expr) => match &**expr {
+                Equal(lhs, rhs) => binary(f, scope, "!=", lhs, rhs),
+                expr => {
+                    write!(f, "!")?;
+                    expr.write(f, precedence, scope)
+                }
+            },
+            Min(lhs, rhs) => call(f, scope, "min", lhs, rhs),
+            Max(lhs, rhs) => call(f, scope, "max", lhs, rhs),
+            Or(lhs, rhs) if logical => binary(f, scope, "||", lhs, rhs),
+            And(lhs, rhs) if logical => binary(f, scope, "&&", lhs, rhs),
+            Or(lhs, rhs) => binary(f, scope, "|", lhs, rhs),
+            And(lhs, rhs) => binary(f, scope, "&", lhs, rhs),
+            Gt(lhs, rhs) => binary(f, scope, ">", lhs, rhs),
+            Gte(lhs, rhs) => binary(f, scope, ">=", lhs, rhs),
+            Lt(lhs, rhs) => binary(f, scope, "<", lhs, rhs),
+            Lte(lhs, rhs) => binary(f, scope, "<=", lhs, rhs),
+            Equal(lhs, rhs) => binary(f, scope, "==", lhs, rhs),
+            Shr(lhs, rhs) => binary(f, scope, ">>", lhs, rhs),
+            Add(lhs, rhs) => binary(f, scope, "+", lhs, rhs),
+            Sub(lhs, rhs) => binary(f, scope, "-", lhs, rhs),
+            Mul(lhs, rhs) => binary(f, scope, "*", lhs, rhs),
+            Mod(lhs, rhs) => binary(f, scope, "%", lhs, rhs),
+        }
+    }
+}
+
+/**
+ * Prints the expression in the textual syntax, `parse_expr` reads it back into the same tree.
+ */
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0, &mut Vec::new())
+    }
+}
+
+impl Statement {
+    fn write(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        use Statement::*;
+
+        match self {
+            Void => write!(f, "{{}}"),
+            SetResult(expr) => write!(f, "result = {expr};"),
+            IfThenElse {
+                condition,
+                if_true_then,
+                if_false_then,
+            } => {
+                write!(f, "if {condition} ")?;
+                if_true_then.write_block(f, indent)?;
+                match &**if_false_then {
+                    Void => Ok(()),
+                    statement @ IfThenElse { .. } => {
+                        write!(f, " else ")?;
+                        statement.write(f, indent)
+                    }
+                    statement => {
+                        write!(f, " else ")?;
+                        statement.write_block(f, indent)
+                    }
+                }
+            }
+        }
+    }
+
+    fn write_block(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        if let Statement::Void = self {
+            return write!(f, "{{}}");
+        }
+
+        writeln!(f, "{{")?;
+        write!(f, "{:width$}", "", width = (indent + 1) * 4)?;
+        self.write(f, indent + 1)?;
+        writeln!(f)?;
+        write!(f, "{:width$}}}", "", width = indent * 4)
+    }
+}
+
+/**
+ * Prints the statement in the textual syntax with one statement per line, `parse` reads it back
+ * into the same tree.
+ */
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/**
+ * Prints the neighborhood the way it is written after `neighborhood` in a rule.
+ */
+impl fmt::Display for Neighborhood {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Neighborhood::Moore { radius } => write!(f, "moore({radius})"),
+            Neighborhood::VonNeumann { radius } => write!(f, "von_neumann({radius})"),
+            Neighborhood::Hexagonal => write!(f, "hexagonal"),
+            Neighborhood::Custom(offsets) => {
+                let offsets = offsets
+                    .iter()
+                    .map(|NeighborOffset { x, y, weight }| match weight {
+                        1 => format!("({x}, {y})"),
+                        _ => format!("({x}, {y}, {weight})"),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "custom [{}]", offsets.join(", "))
+            }
+        }
+    }
+}
+
+/**
+ * Prints the rule in the textual syntax, headers that have their default value are left out.
+ * `parse_rule` reads it back into the same rule.
+ */
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.neighborhood != Neighborhood::default() {
+            writeln!(f, "neighborhood {}", self.neighborhood)?;
+        }
+        if self.states != 2 {
+            writeln!(f, "states {}", self.states)?;
+        }
+        writeln!(f, "{}", self.statement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        dsl::{
+            exprs::*, library_rules, load_rule_from, parse_expr, parse_rule, rulesets,
+            statements::*, Neighborhood, Rule,
+        },
+        shaders::FileSystemAssets,
+    };
+
+    fn assert_round_trip(rule: &Rule) {
+        let printed = rule.to_string();
+        let parsed = parse_rule(&printed)
+            .unwrap_or_else(|err| panic!("{err} in the printed rule\n{printed}"));
+        assert_eq!(&parsed, rule, "printed as\n{printed}");
+    }
+
+    #[test]
+    fn catalog_rules_round_trip() {
+        for (name, _) in rulesets::CATALOG {
+            assert_round_trip(&rulesets::by_name(name).unwrap());
+        }
+        assert_round_trip(&rulesets::conways_game_of_life().into());
+    }
+
+    #[test]
+    fn library_rules_round_trip() {
+        let assets = FileSystemAssets::default();
+        let names = library_rules(&assets).unwrap();
+        assert!(!names.is_empty());
+        for name in names {
+            assert_round_trip(&load_rule_from(&assets, &name).unwrap());
+        }
+    }
+
+    #[test]
+    fn parentheses_follow_precedence() {
+        let exprs = [
+            mul(add(neighbors(), const_u32(1)), const_u32(2)),
+            sub(const_u32(8), sub(neighbors(), const_u32(1))),
+            sub(sub(const_u32(8), neighbors()), const_u32(1)),
+            equal(equal(neighbors(), const_u32(2)), alive()),
+            and(or(alive(), not(alive())), alive()),
+            not(and(neighbor_mask(), const_u32(3))),
+            or(
+                and(neighbor_mask(), const_u32(1)),
+                shr(state(), const_u32(1)),
+            ),
+            and(alive(), const_u32(1)),
+            add(let_in("a", neighbors(), var("a")), const_u32(1)),
+            min(
+                let_in("a", alive(), and(var("a"), alive())),
+                max(state(), const_u32(2)),
+            ),
+            not(not(equal(neighbors(), const_u32(3)))),
+        ];
+        for expr in exprs {
+            let printed = expr.to_string();
+            assert_eq!(parse_expr(&printed), Ok(expr), "printed as {printed}");
+        }
+        assert_eq!(
+            mul(add(neighbors(), const_u32(1)), const_u32(2)).to_string(),
+            "(n + 1) * 2"
+        );
+        // Integers are combined bit by bit, booleans logically
+        assert_eq!(
+            and(neighbor_mask(), const_u32(1)).to_string(),
+            "neighbor_mask & 1"
+        );
+        assert_eq!(
+            or(alive(), equal(neighbors(), const_u32(3))).to_string(),
+            "alive || n == 3"
+        );
+    }
+
+    #[test]
+    fn headers_round_trip() {
+        assert_round_trip(
+            &Rule::new(if_then_else(
+                equal(state(), const_u32(0)),
+                set_result(equal(neighbors(), const_u32(2))),
+                if_then_else(
+                    equal(state(), const_u32(1)),
+                    set_result(const_u32(2)),
+                    void(),
+                ),
+            ))
+            .with_states(3)
+            .with_neighborhood(Neighborhood::VonNeumann { radius: 2 }),
+        );
+        assert_round_trip(&Rule::new(void()).with_neighborhood(Neighborhood::Hexagonal));
+    }
+}
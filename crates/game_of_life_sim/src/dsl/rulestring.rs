@@ -0,0 +1,526 @@
+use super::{
+    exprs::*, hensel, statements::*, Expr, IsotropicRule, Neighborhood, ParseError, Rule, Statement,
+};
+
+/**
+ * A Life-like rule described by the neighbor counts that cause a dead cell to be born and the
+ * neighbor counts that let a living cell survive.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LifeLikeRule {
+    pub birth: [bool; 9],
+    pub survival: [bool; 9],
+}
+
+impl LifeLikeRule {
+    /**
+     * Parse a rulestring in birth/survival notation (`B3/S23`, `B36/S23`, `B2/S`) or in the
+     * older survival/birth notation (`23/3`). The letters are case insensitive and the slash may
+     * be omitted when letters are present (`B3S23`).
+     */
+    pub fn parse(rulestring: &str) -> Result<Self, ParseError> {
+        let rulestring = rulestring.trim();
+        let error = |message: String, index: usize| ParseError {
+            message,
+            line: 1,
+            column: index + 1,
+        };
+
+        if rulestring.is_empty() {
+            return Err(error("empty rulestring".to_string(), 0));
+        }
+
+        let mut rule = LifeLikeRule::default();
+        let has_letters = rulestring
+            .chars()
+            .any(|c| matches!(c.to_ascii_uppercase(), 'B' | 'S'));
+
+        if !has_letters {
+            // Older S/B notation, e.g. `23/3`
+            let Some((survival, birth)) = rulestring.split_once('/') else {
+                return Err(error(
+                    "expected `S/B` notation with a single `/`".to_string(),
+                    0,
+                ));
+            };
+            let birth_offset = survival.len() + 1;
+            parse_counts(survival, 0, &mut rule.survival)?;
+            parse_counts(birth, birth_offset, &mut rule.birth)?;
+            return Ok(rule);
+        }
+
+        let (mut seen_birth, mut seen_survival) = (false, false);
+        let mut chars = rulestring.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            let (counts, seen) = match c.to_ascii_uppercase() {
+                'B' => (&mut rule.birth, &mut seen_birth),
+                'S' => (&mut rule.survival, &mut seen_survival),
+                '/' => continue,
+                _ => return Err(error(format!("unexpected character `{c}`"), index)),
+            };
+            if *seen {
+                return Err(error(format!("`{c}` appears more than once"), index));
+            }
+            *seen = true;
+
+            let start = index + c.len_utf8();
+            let mut end = start;
+            while let Some(&(i, d)) = chars.peek() {
+                if !d.is_ascii_digit() {
+                    break;
+                }
+                end = i + d.len_utf8();
+                chars.next();
+            }
+            parse_counts(&rulestring[start..end], start, counts)?;
+        }
+
+        if !seen_birth || !seen_survival {
+            return Err(error(
+                "expected both a `B` and an `S` section".to_string(),
+                0,
+            ));
+        }
+
+        Ok(rule)
+    }
+
+    /**
+     * Build the statement that implements this rule, a living cell survives if its neighbor
+     * count is in the survival set, otherwise a dead cell is born if its neighbor count is in
+     * the birth set.
+     */
+    pub fn to_statement(&self) -> Statement {
+        if_then_else(
+            alive(),
+            set_result(neighbors_in(&self.survival)),
+            set_result(neighbors_in(&self.birth)),
+        )
+    }
+
+    /**
+     * Format this rule in canonical `B/S` notation.
+     */
+    pub fn to_rulestring(&self) -> String {
+        let digits = |counts: &[bool; 9]| -> String {
+            (0..9)
+                .filter(|&n| counts[n])
+                .map(|n| char::from(b'0' + n as u8))
+                .collect()
+        };
+        format!("B{}/S{}", digits(&self.birth), digits(&self.survival))
+    }
+}
+
+fn parse_counts(digits: &str, offset: usize, counts: &mut [bool; 9]) -> Result<(), ParseError> {
+    for (index, c) in digits.char_indices() {
+        match c.to_digit(10) {
+            Some(n) if n <= 8 => counts[n as usize] = true,
+            _ => {
+                return Err(ParseError {
+                    message: format!("`{c}` is not a neighbor count between 0 and 8"),
+                    line: 1,
+                    column: offset + index + 1,
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+/**
+ * An expression that is 1 when the neighbor count is one of the counts set in `counts`.
+ */
+fn neighbors_in(counts: &[bool; 9]) -> Expr {
+    (0..9u32)
+        .filter(|&n| counts[n as usize])
+        .map(|n| equal(neighbors(), const_u32(n)))
+        .reduce(or)
+        .unwrap_or(const_u32(0))
+}
+
+/**
+ * A Generations rule, a Life-like rule where a living cell that does not survive passes through
+ * `states - 2` dying states before it is dead. Dying cells do not count as neighbors and cannot be
+ * born again until they are dead.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerationsRule {
+    pub life: LifeLikeRule,
+    pub states: u32,
+}
+
+impl GenerationsRule {
+    /**
+     * Parse a rulestring in `B/S/C` notation (`B2/S/C3`) or the older `S/B/C` notation (`/2/3`),
+     * where the last section is the total number of states including dead and alive.
+     */
+    pub fn parse(rulestring: &str) -> Result<Self, ParseError> {
+        let rulestring = rulestring.trim();
+        let Some((life, states)) = rulestring.rsplit_once('/') else {
+            return Err(ParseError {
+                message: "expected a `/C` section with the number of states".to_string(),
+                line: 1,
+                column: 1,
+            });
+        };
+
+        let column = life.len() + 2;
+        let digits = states.trim_start_matches(['C', 'c']);
+        let states = digits.parse::<u32>().map_err(|_| ParseError {
+            message: format!("expected the number of states, found `{states}`"),
+            line: 1,
+            column,
+        })?;
+        if states < 2 {
+            return Err(ParseError {
+                message: "a rule needs at least 2 states".to_string(),
+                line: 1,
+                column,
+            });
+        }
+
+        Ok(Self {
+            life: LifeLikeRule::parse(life)?,
+            states,
+        })
+    }
+
+    /**
+     * Build the rule: dead cells are born into state 1, living cells either stay in state 1 or
+     * start dying, and dying cells age by one state each generation until they wrap back to 0.
+     */
+    pub fn to_rule(&self) -> Rule {
+        if self.states == 2 {
+            return Rule::new(self.life.to_statement());
+        }
+
+        let statement = if_then_else(
+            equal(state(), const_u32(0)),
+            set_result(neighbors_in(&self.life.birth)),
+            if_then_else(
+                equal(state(), const_u32(1)),
+                set_result(sub(const_u32(2), neighbors_in(&self.life.survival))),
+                set_result(modulo(add(state(), const_u32(1)), const_u32(self.states))),
+            ),
+        );
+
+        Rule::new(statement).with_states(self.states)
+    }
+}
+
+/**
+ * A Larger than Life rule, where cells are born or survive when the number of living cells in a
+ * large Moore or von Neumann neighborhood falls within a range.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LargerThanLifeRule {
+    pub radius: u32,
+    /// Whether the cell itself is counted as one of its neighbors
+    pub include_center: bool,
+    pub survival: (u32, u32),
+    pub birth: (u32, u32),
+    pub von_neumann: bool,
+}
+
+impl LargerThanLifeRule {
+    /**
+     * Parse a rulestring in the `Rr,Cc,Mm,Smin..max,Bmin..max,Nn` notation used by Golly, e.g.
+     * `R5,C0,M1,S34..58,B34..45,NM`. Only two state rules are supported, so `C` must be 0 or 2.
+     */
+    pub fn parse(rulestring: &str) -> Result<Self, ParseError> {
+        let mut rule = LargerThanLifeRule {
+            radius: 1,
+            include_center: false,
+            survival: (2, 3),
+            birth: (3, 3),
+            von_neumann: false,
+        };
+
+        let mut offset = 0;
+        for part in rulestring.split(',') {
+            let error = |message: String| ParseError {
+                message,
+                line: 1,
+                column: offset + 1,
+            };
+            let trimmed = part.trim();
+            let Some(key) = trimmed.chars().next() else {
+                return Err(error("empty section".to_string()));
+            };
+            let value = &trimmed[key.len_utf8()..];
+            let number = |value: &str| {
+                value
+                    .parse::<u32>()
+                    .map_err(|_| error(format!("expected a number, found `{value}`")))
+            };
+            let range = |value: &str| {
+                let (min, max) = value.split_once("..").ok_or_else(|| {
+                    error(format!("expected a range like `2..3`, found `{value}`"))
+                })?;
+                Ok::<_, ParseError>((number(min)?, number(max)?))
+            };
+
+            match key.to_ascii_uppercase() {
+                'R' => rule.radius = number(value)?,
+                'C' => match number(value)? {
+                    0 | 2 => {}
+                    _ => return Err(error("only two state rules are supported".to_string())),
+                },
+                'M' => rule.include_center = number(value)? == 1,
+                'S' => rule.survival = range(value)?,
+                'B' => rule.birth = range(value)?,
+                'N' => match value.to_ascii_uppercase().as_str() {
+                    "M" => rule.von_neumann = false,
+                    "N" => rule.von_neumann = true,
+                    _ => return Err(error(format!("unknown neighborhood `{value}`"))),
+                },
+                _ => return Err(error(format!("unknown section `{key}`"))),
+            }
+
+            offset += part.len() + 1;
+        }
+
+        Ok(rule)
+    }
+
+    pub fn neighborhood(&self) -> Neighborhood {
+        if self.von_neumann {
+            Neighborhood::VonNeumann {
+                radius: self.radius,
+            }
+        } else {
+            Neighborhood::Moore {
+                radius: self.radius,
+            }
+        }
+    }
+
+    /**
+     * Build the rule, when the center is included a living cell counts itself so the survival
+     * range is tested against one more than the neighbor count.
+     */
+    pub fn to_rule(&self) -> Rule {
+        let in_range = |count: Expr, (min, max): (u32, u32)| {
+            and(
+                gte(count.clone(), const_u32(min)),
+                lte(count, const_u32(max)),
+            )
+        };
+        let survival_count = if self.include_center {
+            add(neighbors(), const_u32(1))
+        } else {
+            neighbors()
+        };
+
+        Rule::new(if_then_else(
+            alive(),
+            set_result(in_range(survival_count, self.survival)),
+            set_result(in_range(neighbors(), self.birth)),
+        ))
+        .with_neighborhood(self.neighborhood())
+    }
+}
+
+/**
+ * Parse a rulestring and build the rule that implements it. Life-like rulestrings may end in `H`
+ * for the hexagonal neighborhood or `V` for the von Neumann neighborhood (`B2/S34H`), rulestrings
+ * with a number of states are read as Generations rules (`B2/S/C3`, `/2/3`), rulestrings with
+ * Hensel letters are read as isotropic non-totalistic rules (`B3/S2-i34q`) and rulestrings
+ * starting with `R` are read as Larger than Life rules.
+ */
+pub fn parse_rulestring(rulestring: &str) -> Result<Rule, ParseError> {
+    let rulestring = rulestring.trim();
+    if rulestring.starts_with(['R', 'r']) {
+        return LargerThanLifeRule::parse(rulestring).map(|rule| rule.to_rule());
+    }
+
+    // `c` is also a Hensel letter, so only a section of its own starting with it is a state count
+    let is_generations = rulestring.matches('/').count() == 2
+        || rulestring
+            .split('/')
+            .skip(1)
+            .any(|part| part.starts_with(['C', 'c']));
+    if is_generations {
+        return GenerationsRule::parse(rulestring).map(|rule| rule.to_rule());
+    }
+
+    let is_isotropic = rulestring.contains(|c: char| c == '-' || hensel::is_letter(c));
+    if is_isotropic {
+        return IsotropicRule::parse(rulestring).map(|rule| rule.to_rule());
+    }
+
+    let (rulestring, neighborhood) = match rulestring.chars().last() {
+        Some('H' | 'h') => (&rulestring[..rulestring.len() - 1], Neighborhood::Hexagonal),
+        Some('V' | 'v') => (
+            &rulestring[..rulestring.len() - 1],
+            Neighborhood::VonNeumann { radius: 1 },
+        ),
+        _ => (rulestring, Neighborhood::default()),
+    };
+
+    let rule = LifeLikeRule::parse(rulestring)?;
+    let max_count = neighborhood.max_count() as usize;
+    if let Some(count) = (max_count + 1..9).find(|&n| rule.birth[n] || rule.survival[n]) {
+        return Err(ParseError {
+            message: format!("neighbor count {count} is impossible in this neighborhood"),
+            line: 1,
+            column: 1,
+        });
+    }
+
+    Ok(Rule::new(rule.to_statement()).with_neighborhood(neighborhood))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::rulesets::CATALOG;
+
+    fn counts(counts: &[usize]) -> [bool; 9] {
+        let mut set = [false; 9];
+        for &n in counts {
+            set[n] = true;
+        }
+        set
+    }
+
+    fn column(result: Result<impl std::fmt::Debug, ParseError>) -> (usize, String) {
+        let err = result.unwrap_err();
+        (err.column, err.message)
+    }
+
+    #[test]
+    fn life_like_notations() {
+        let conway = LifeLikeRule {
+            birth: counts(&[3]),
+            survival: counts(&[2, 3]),
+        };
+        for rulestring in ["B3/S23", "b3/s23", "B3S23", "S23/B3", "23/3", " B3/S23 "] {
+            assert_eq!(
+                LifeLikeRule::parse(rulestring).unwrap(),
+                conway,
+                "{rulestring}"
+            );
+        }
+        assert_eq!(conway.to_rulestring(), "B3/S23");
+
+        let seeds = LifeLikeRule::parse("B2/S").unwrap();
+        assert_eq!(seeds.survival, [false; 9]);
+        assert_eq!(seeds.to_rulestring(), "B2/S");
+        assert_eq!(LifeLikeRule::parse("/2").unwrap(), seeds);
+    }
+
+    #[test]
+    fn count_errors_point_at_the_character() {
+        let (column_of, message) = column(LifeLikeRule::parse("B9/S23"));
+        assert_eq!(column_of, 2);
+        assert!(message.contains("`9`"), "{message}");
+        assert_eq!(column(LifeLikeRule::parse("B3/S239")).0, 7);
+        assert_eq!(column(LifeLikeRule::parse("23/9")).0, 4);
+        assert_eq!(column(LifeLikeRule::parse("B3/X2")).0, 4);
+        assert_eq!(column(LifeLikeRule::parse("B3/b2")).0, 4);
+        assert_eq!(column(LifeLikeRule::parse("B3")).0, 1);
+        assert_eq!(column(LifeLikeRule::parse("")).0, 1);
+    }
+
+    #[test]
+    fn generations_notations() {
+        let star_wars = GenerationsRule::parse("B2/S345/C4").unwrap();
+        assert_eq!(star_wars.states, 4);
+        assert_eq!(star_wars.life, LifeLikeRule::parse("B2/S345").unwrap());
+        assert_eq!(GenerationsRule::parse("345/2/4").unwrap(), star_wars);
+        assert_eq!(GenerationsRule::parse("b2/s345/c4").unwrap(), star_wars);
+
+        let rule = star_wars.to_rule();
+        assert_eq!(rule.states, 4);
+        // Dying cells age until they are dead and can't be born again before
+        let next = |state, count| rule.statement.eval_state(state, count);
+        assert_eq!((next(0, 2), next(1, 3), next(1, 2)), (1, 1, 2));
+        assert_eq!((next(2, 2), next(3, 2)), (3, 0));
+
+        // Two states are a plain Life-like rule
+        assert_eq!(
+            GenerationsRule::parse("B3/S23/C2").unwrap().to_rule(),
+            parse_rulestring("B3/S23").unwrap()
+        );
+
+        assert_eq!(column(GenerationsRule::parse("B2/S/C1")).0, 6);
+        assert_eq!(column(GenerationsRule::parse("B2/S/Cx")).0, 6);
+        assert_eq!(column(GenerationsRule::parse("B2S")).0, 1);
+    }
+
+    #[test]
+    fn larger_than_life_notation() {
+        let bosco = LargerThanLifeRule::parse("R5,C0,M1,S34..58,B34..45,NM").unwrap();
+        assert_eq!(
+            bosco,
+            LargerThanLifeRule {
+                radius: 5,
+                include_center: true,
+                survival: (34, 58),
+                birth: (34, 45),
+                von_neumann: false,
+            }
+        );
+        assert_eq!(
+            bosco.to_rule().neighborhood,
+            Neighborhood::Moore { radius: 5 }
+        );
+        assert_eq!(
+            parse_rulestring("r5,c0,m1,s34..58,b34..45,nm").unwrap(),
+            bosco.to_rule()
+        );
+
+        let diamond = LargerThanLifeRule::parse("R2,C2,M0,S1..2,B2..2,NN").unwrap();
+        assert_eq!(
+            diamond.to_rule().neighborhood,
+            Neighborhood::VonNeumann { radius: 2 }
+        );
+
+        assert_eq!(column(LargerThanLifeRule::parse("R5,C3")).0, 4);
+        assert_eq!(column(LargerThanLifeRule::parse("R5,S3")).0, 4);
+        assert_eq!(column(LargerThanLifeRule::parse("R5,M1,NX")).0, 7);
+        assert_eq!(column(LargerThanLifeRule::parse("R5,,B1..2")).0, 4);
+    }
+
+    #[test]
+    fn neighborhood_suffixes() {
+        let hex = parse_rulestring("B2/S34H").unwrap();
+        assert_eq!(hex.neighborhood, Neighborhood::Hexagonal);
+        assert_eq!(parse_rulestring("b2/s34h").unwrap(), hex);
+
+        let von_neumann = parse_rulestring("B3/S23V").unwrap();
+        assert_eq!(
+            von_neumann.neighborhood,
+            Neighborhood::VonNeumann { radius: 1 }
+        );
+        assert_eq!(parse_rulestring("b3s23v").unwrap(), von_neumann);
+
+        let (_, message) = column(parse_rulestring("B3/S27H"));
+        assert!(message.contains("count 7"), "{message}");
+        assert!(parse_rulestring("B5/S23V").is_err());
+    }
+
+    #[test]
+    fn rulestrings_pick_their_notation() {
+        assert_eq!(parse_rulestring("B3/S23").unwrap().states, 2);
+        assert_eq!(parse_rulestring("/2/3").unwrap().states, 3);
+        assert_eq!(parse_rulestring("B2/S/C3").unwrap().states, 3);
+        // `c` is a Hensel letter as well as the start of a state count
+        assert_eq!(parse_rulestring("B2c/S").unwrap().states, 2);
+        assert!(parse_rulestring("B3/S2-i34q")
+            .unwrap()
+            .optimize()
+            .reads_neighbor_mask());
+    }
+
+    #[test]
+    fn catalog_rulestrings_parse() {
+        for (name, rulestring) in CATALOG {
+            let rule = parse_rulestring(rulestring)
+                .unwrap_or_else(|err| panic!("{name}: {rulestring}: {err}"));
+            assert_eq!(rule.check(), Ok(()), "{name}");
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use super::Rule;
+
+/**
+ * The largest table a rule is turned into, 2 states over a radius 15 Moore neighborhood or a
+ * Generations rule with a few hundred states over the 3x3 neighborhood still fit.
+ */
+pub const MAX_TABLE_ENTRIES: usize = 4096;
+
+/**
+ * How the statement of a rule is turned into wgsl.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Translate the statement into wgsl expressions with `Statement::to_shader`
+    Expression,
+    /// Evaluate the rule up front and embed the results as an array in the shader
+    InlineTable,
+    /// Evaluate the rule up front and read the results from the `rule_table` storage buffer, so
+    /// a rule with the same neighborhood and number of states can be swapped in by rewriting
+    /// the buffer instead of recompiling the shader
+    #[default]
+    StorageTable,
+}
+
+impl Backend {
+    /**
+     * The backend to build `rule` with, the table backends fall back to expressions when the rule
+     * is too large to turn into a table. Like the shader this looks at the optimized rule.
+     */
+    pub fn for_rule(self, rule: &Rule) -> Backend {
+        match self {
+            Backend::InlineTable | Backend::StorageTable if !RuleTable::fits(rule) => {
+                Backend::Expression
+            }
+            backend => backend,
+        }
+    }
+}
+
+/**
+ * The result of a rule for every combination of state and neighbor count, evaluated on the CPU.
+ * Entries are stored row by row, one row of `neighbors` entries per state.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleTable {
+    pub states: u32,
+    /// The number of entries per state, one more than the largest neighbor count
+    pub neighbors: u32,
+    pub entries: Vec<u32>,
+}
+
+impl RuleTable {
+    /**
+     * Evaluate `rule` for every input, returns `None` if the table would have more than
+     * `MAX_TABLE_ENTRIES` entries. The rule is optimized first like the shader does, so a table
+     * is built whenever the shader reads one. The rule should pass `check` first.
+     */
+    pub fn build(rule: &Rule) -> Option<Self> {
+        let rule = rule.optimize();
+        if !Self::fits_optimized(&rule) {
+            return None;
+        }
+        let neighbors = rule.neighborhood.max_count() + 1;
+
+        let statement = &rule.statement;
+        let entries = (0..rule.states)
+            .flat_map(|state| (0..neighbors).map(move |count| statement.eval_state(state, count)))
+            .collect();
+
+        Some(Self {
+            states: rule.states,
+            neighbors,
+            entries,
+        })
+    }
+
+    /**
+     * Whether the table of `rule` has at most `MAX_TABLE_ENTRIES` entries. Rules that still read
+     * the neighbor mask once optimized depend on more than the state and neighbor count and
+     * never fit.
+     */
+    pub fn fits(rule: &Rule) -> bool {
+        Self::fits_optimized(&rule.optimize())
+    }
+
+    fn fits_optimized(rule: &Rule) -> bool {
+        !rule.reads_neighbor_mask()
+            && rule
+                .neighborhood
+                .max_count()
+                .checked_add(1)
+                .and_then(|neighbors| (rule.states as usize).checked_mul(neighbors as usize))
+                .is_some_and(|size| size <= MAX_TABLE_ENTRIES)
+    }
+
+    pub fn get(&self, state: u32, neighbors: u32) -> u32 {
+        self.entries[(state * self.neighbors + neighbors) as usize]
+    }
+
+    /**
+     * The wgsl declaration of the table as a module level array named `RULE_TABLE`. Constant
+     * arrays can't be indexed with a runtime value by naga, so it is a private variable.
+     */
+    pub fn to_shader(&self) -> String {
+        let entries = self
+            .entries
+            .iter()
+            .map(|entry| format!("{entry}u"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "var<private> RULE_TABLE: array<u32, {len}> = array<u32, {len}>({entries});",
+            len = self.entries.len()
+        )
+    }
+
+    /**
+     * The statement that reads the result from the inline table.
+     */
+    pub fn lookup_shader(&self) -> String {
+        format!(
+            "result = RULE_TABLE[min(state, {}u) * {}u + min(num_neighbors, {}u)];",
+            self.states.saturating_sub(1),
+            self.neighbors,
+            self.neighbors - 1
+        )
+    }
+
+    /**
+     * The contents of the `rule_table` storage buffer, the number of states and the row length
+     * followed by the entries.
+     */
+    pub fn to_buffer(&self) -> Vec<u32> {
+        let mut buffer = Vec::with_capacity(self.entries.len() + 2);
+        buffer.push(self.states);
+        buffer.push(self.neighbors);
+        buffer.extend(&self.entries);
+        buffer
+    }
+
+    /**
+     * The size in bytes of a storage buffer that can hold any table.
+     */
+    pub fn buffer_size() -> u64 {
+        ((MAX_TABLE_ENTRIES + 2) * std::mem::size_of::<u32>()) as u64
+    }
+
+    /**
+     * The statement that reads the result from the `rule_table` storage buffer. The size of the
+     * table is read from the buffer, so the shader works with any table.
+     */
+    pub fn storage_lookup_shader() -> String {
+        "result = rule_table.entries[min(state, rule_table.states - 1u) * rule_table.neighbors \
+         + min(num_neighbors, rule_table.neighbors - 1u)];"
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::{parse_rule, parse_rulestring, rulesets, Neighborhood};
+
+    #[test]
+    fn tables_match_the_evaluated_rule() {
+        for name in ["highlife", "star_wars"] {
+            let rule = rulesets::by_name(name).unwrap();
+            let table = RuleTable::build(&rule).unwrap();
+            assert_eq!((table.states, table.neighbors), (rule.states, 9));
+            assert_eq!(table.entries.len(), (rule.states * 9) as usize);
+            for state in 0..rule.states {
+                for count in 0..=8 {
+                    assert_eq!(
+                        table.get(state, count),
+                        rule.statement.eval_state(state, count),
+                        "{name}: state {state}, {count} neighbors"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rules_that_read_the_mask_or_are_too_large_do_not_fit() {
+        let hensel = parse_rulestring("B2a/S12").unwrap();
+        assert!(!RuleTable::fits(&hensel));
+        assert_eq!(RuleTable::build(&hensel), None);
+        assert_eq!(Backend::InlineTable.for_rule(&hensel), Backend::Expression);
+
+        let conway: Rule = rulesets::conways_game_of_life().into();
+        // 2 states by 961 counts fits, 2 by 3721 doesn't
+        let wide = conway
+            .clone()
+            .with_neighborhood(Neighborhood::Moore { radius: 15 });
+        assert!(RuleTable::fits(&wide));
+        let wider = conway
+            .clone()
+            .with_neighborhood(Neighborhood::Moore { radius: 30 });
+        assert!(!RuleTable::fits(&wider));
+        assert_eq!(Backend::StorageTable.for_rule(&wider), Backend::Expression);
+
+        let many_states = conway.with_states(1000);
+        assert!(!RuleTable::fits(&many_states));
+    }
+
+    #[test]
+    fn buffers_start_with_the_size_of_the_table() {
+        let table = RuleTable::build(&rulesets::conways_game_of_life().into()).unwrap();
+        let buffer = table.to_buffer();
+        assert_eq!(buffer[..2], [2, 9]);
+        assert_eq!(buffer[2..], table.entries);
+        assert_eq!(buffer[2 + 9 + 3], 1);
+        assert!(buffer.len() as u64 * 4 <= RuleTable::buffer_size());
+    }
+
+    #[test]
+    fn lookups_clamp_the_index() {
+        let table = RuleTable::build(&rulesets::by_name("star_wars").unwrap()).unwrap();
+        assert_eq!(
+            table.lookup_shader(),
+            "result = RULE_TABLE[min(state, 3u) * 9u + min(num_neighbors, 8u)];"
+        );
+        assert!(table
+            .to_shader()
+            .starts_with("var<private> RULE_TABLE: array<u32, 36> = array<u32, 36>(0u, 0u, 1u, "));
+
+        let lookup = RuleTable::storage_lookup_shader();
+        assert!(lookup.contains("min(state, rule_table.states - 1u)"));
+        assert!(lookup.contains("min(num_neighbors, rule_table.neighbors - 1u)"));
+    }
+
+    #[test]
+    fn mask_reads_the_optimizer_removes_still_fit() {
+        // No mask is larger than 255, so the first branch never runs
+        let rule = parse_rule(
+            "if neighbor_mask > 255 {\n    result = 1;\n} else if alive {\n    \
+             result = n == 2 || n == 3;\n} else {\n    result = n == 3;\n}",
+        )
+        .unwrap();
+        assert!(rule.statement.reads(&crate::dsl::Expr::NeighborMask));
+        assert!(!rule.optimize().reads_neighbor_mask());
+
+        assert!(RuleTable::fits(&rule));
+        assert_eq!(Backend::StorageTable.for_rule(&rule), Backend::StorageTable);
+        assert_eq!(
+            RuleTable::build(&rule),
+            RuleTable::build(&rulesets::conways_game_of_life().into())
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use glass::{
+    wgpu,
+    window::GlassWindow,
+    winit::{self, dpi::PhysicalSize},
+};
+
+use crate::dsl;
+
+/// Draws egui windows over the canvas. It feeds egui the events of the window, runs the ui in
+/// `update` and paints what the ui produced in `render`.
+pub struct Gui {
+    context: egui::Context,
+    state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    /// The shapes of the last run of the ui, painted by the next `render`
+    primitives: Vec<egui::ClippedPrimitive>,
+    /// The font and image textures to upload or free before the next `render`
+    textures: egui::TexturesDelta,
+}
+
+impl Gui {
+    pub fn new(
+        event_loop: &winit::event_loop::EventLoop<()>,
+        device: &wgpu::Device,
+        window: &winit::window::Window,
+    ) -> Self {
+        let mut state = egui_winit::State::new(event_loop);
+        state.set_pixels_per_point(window.scale_factor() as f32);
+        state.set_max_texture_side(device.limits().max_texture_dimension_2d as usize);
+
+        Self {
+            context: egui::Context::default(),
+            state,
+            renderer: egui_wgpu::Renderer::new(device, GlassWindow::surface_format(), None, 1),
+            primitives: Vec::new(),
+            textures: egui::TexturesDelta::default(),
+        }
+    }
+
+    /// Pass an event of the window to egui, returns whether egui used it and the canvas should
+    /// ignore it, like a click on a panel or a key typed into a text field
+    pub fn on_event(&mut self, event: &winit::event::WindowEvent) -> bool {
+        self.state.on_event(&self.context, event).consumed
+    }
+
+    /// Run the ui for the next frame of `window`
+    pub fn run(&mut self, window: &winit::window::Window, ui: impl FnOnce(&egui::Context)) {
+        let input = self.state.take_egui_input(window);
+        let output = self.context.run(input, ui);
+        self.state
+            .handle_platform_output(window, &self.context, output.platform_output);
+        self.primitives = self.context.tessellate(output.shapes);
+        self.textures.append(output.textures_delta);
+    }
+
+    /// Paint the ui of the last `run` over what `view` already shows
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size: PhysicalSize<u32>,
+    ) {
+        let textures = std::mem::take(&mut self.textures);
+        for (id, image_delta) in &textures.set {
+            self.renderer
+                .update_texture(device, queue, *id, image_delta);
+        }
+
+        let screen = egui_wgpu::renderer::ScreenDescriptor {
+            size_in_pixels: [size.width, size.height],
+            pixels_per_point: self.state.pixels_per_point(),
+        };
+        // Paint callbacks would record their own commands, the ui has none
+        let callbacks =
+            self.renderer
+                .update_buffers(device, queue, encoder, &self.primitives, &screen);
+        queue.submit(callbacks);
+
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Gui"),
+                depth_stencil_attachment: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+            });
+            self.renderer.render(&mut rpass, &self.primitives, &screen);
+        }
+
+        for id in &textures.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
+
+/// A rule picked in the rule panel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleChoice {
+    /// The rule at this index of `dsl::rulesets::CATALOG`
+    Catalog(usize),
+    /// A rule of the library by name, see `dsl::load_rule`
+    Library(String),
+    /// What was typed into the rulestring field, anything `dsl::find_rule` understands
+    Typed(String),
+}
+
+/// A window to switch the rule while running: a list of the rules in the catalog and the library
+/// and a field for a rulestring, or the name or path of a rule
+#[derive(Debug, Default)]
+pub struct RulePanel {
+    /// The names of the rules in the library
+    library: Vec<String>,
+    rulestring: String,
+    /// The name of the last rule picked from outside the catalog and the rule it stands for
+    pub picked: Option<(String, dsl::Rule)>,
+    /// Why the last rule picked failed to load or build, the previous rule keeps running
+    pub error: Option<String>,
+}
+
+impl RulePanel {
+    pub fn new(library: Vec<String>) -> Self {
+        Self {
+            library,
+            ..Self::default()
+        }
+    }
+
+    /// Show the panel for the rule that is running, returns the rule picked in this frame
+    pub fn show(
+        &mut self,
+        context: &egui::Context,
+        rule: &dsl::Rule,
+        catalog_index: Option<usize>,
+    ) -> Option<RuleChoice> {
+        let selected = match (catalog_index, &self.picked) {
+            (Some(index), _) => dsl::rulesets::CATALOG[index].0,
+            (None, Some((name, picked))) if picked == rule => name,
+            (None, _) => "Custom rule",
+        };
+
+        let mut choice = None;
+        egui::Window::new("Rule").show(context, |ui| {
+            egui::ComboBox::from_id_source("rule")
+                .selected_text(selected)
+                .width(200.0)
+                .show_ui(ui, |ui| {
+                    for (index, (name, rulestring)) in dsl::rulesets::CATALOG.iter().enumerate() {
+                        let label = ui.selectable_label(catalog_index == Some(index), *name);
+                        if label.on_hover_text(*rulestring).clicked() {
+                            choice = Some(RuleChoice::Catalog(index));
+                        }
+                    }
+                    ui.separator();
+                    for name in &self.library {
+                        if ui.selectable_label(selected == name, name).clicked() {
+                            choice = Some(RuleChoice::Library(name.clone()));
+                        }
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                let field = ui
+                    .text_edit_singleline(&mut self.rulestring)
+                    .on_hover_text("A rulestring like B36/S23, or the name or path of a rule");
+                let entered =
+                    field.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+                let rulestring = self.rulestring.trim();
+                if (ui.button("Apply").clicked() || entered) && !rulestring.is_empty() {
+                    choice = Some(RuleChoice::Typed(rulestring.to_string()));
+                }
+            });
+
+            if let Some(error) = &self.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        });
+        choice
+    }
+}
+
+/// Show the shaders that failed to reload and why over the bottom left corner of the canvas,
+/// nothing is shown without errors
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+pub fn show_shader_errors(context: &egui::Context, errors: &[String]) {
+    if errors.is_empty() {
+        return;
+    }
+
+    egui::Area::new("shader_errors")
+        .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -8.0])
+        .show(context, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label("Shader errors, the last pipelines that built keep running:");
+                for error in errors {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+            });
+        });
+}
@@ -0,0 +1,87 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use notify::{RecursiveMode, Watcher};
+
+use crate::shaders::ASSET_DIR;
+
+/// Watches `assets` for changes to the files the shaders are composed from
+pub struct ShaderWatcher {
+    // Stops watching when dropped
+    _watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    /// The watched directory as notify reports it, which may have resolved symlinks
+    root: PathBuf,
+    /// Every file each shader is composed from, by shader
+    shader_files: Vec<(&'static str, Vec<String>)>,
+    /// Why the last reload of each shader failed, the pipeline of the old shader is kept until a
+    /// reload succeeds
+    pub errors: HashMap<&'static str, String>,
+}
+
+impl ShaderWatcher {
+    pub fn new(shader_files: Vec<(&'static str, Vec<String>)>) -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(Path::new(ASSET_DIR), RecursiveMode::Recursive)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            root: std::fs::canonicalize(ASSET_DIR).unwrap_or_else(|_| ASSET_DIR.into()),
+            shader_files,
+            errors: HashMap::new(),
+        })
+    }
+
+    /// Replace the files the shaders are composed from, their imports may have changed
+    pub fn set_shader_files(&mut self, shader_files: Vec<(&'static str, Vec<String>)>) {
+        self.shader_files = shader_files;
+    }
+
+    /// Every shader that failed to reload and why, sorted by shader
+    pub fn error_messages(&self) -> Vec<String> {
+        let mut errors = self.errors.iter().collect::<Vec<_>>();
+        errors.sort();
+        errors
+            .into_iter()
+            .map(|(shader, error)| format!("{shader}: {error}"))
+            .collect()
+    }
+
+    /// The shaders composed from a file that changed since the last call
+    pub fn changed_shaders(&self) -> Vec<&'static str> {
+        let changed = self
+            .events
+            .try_iter()
+            .filter_map(|event| match event {
+                Ok(event) if !event.kind.is_access() => Some(event.paths),
+                Ok(_) => None,
+                Err(err) => {
+                    log::warn!("Failed to watch shader files: {err}");
+                    None
+                }
+            })
+            .flatten()
+            .filter_map(|path| self.asset_path(&path))
+            .collect::<HashSet<_>>();
+
+        self.shader_files
+            .iter()
+            .filter(|(_, files)| files.iter().any(|file| changed.contains(file)))
+            .map(|(shader, _)| *shader)
+            .collect()
+    }
+
+    /// The path of a changed file relative to `assets`, as shaders import it
+    fn asset_path(&self, path: &Path) -> Option<String> {
+        let relative = path
+            .strip_prefix(&self.root)
+            .or_else(|_| path.strip_prefix(ASSET_DIR))
+            .ok()?;
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+}
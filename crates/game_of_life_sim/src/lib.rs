@@ -1,9 +1,16 @@
 mod camera;
 mod canvas_data;
-mod dsl;
+pub mod cpu;
+pub mod dsl;
 mod gameloop;
+mod gui;
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+mod hot_reload;
+pub mod pattern;
+pub mod picture;
 mod pipelines;
-mod shaders;
+pub mod shaders;
+pub mod snapshot;
 
 use camera::CAMERA_MOVE_SPEED;
 use gameloop::Time;
@@ -14,12 +21,18 @@ use canvas_data::CanvasData;
 use glam::Vec2;
 use glass::{
     pipelines::QuadPipeline,
+    texture::Texture,
     wgpu,
     window::GlassWindow,
     winit::{self, dpi::PhysicalSize},
     GlassApp, GlassContext, RenderData,
 };
+use pattern::{Pattern, PatternError};
+use picture::PictureError;
 use pipelines::Pipelines;
+use serde::{Deserialize, Serialize};
+pub use shaders::{Diagnostic, Label, Location, Origin, ShaderError};
+use snapshot::{Snapshot, SnapshotError};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
@@ -27,6 +40,24 @@ use wasm_bindgen::prelude::*;
 pub const SIM_SIZE: u32 = 1024;
 pub const WORK_GROUP_SIZE: u32 = 32;
 pub const FPS_60: f32 = 16.0 / 1000.0;
+/// Where the `E` key exports the living cells of the canvas to
+pub const EXPORT_PATH: &str = "game_of_life.rle";
+/// Where `F5` saves a snapshot of the session and `F9` loads it from
+pub const SNAPSHOT_PATH: &str = "game_of_life.snapshot";
+/// Where the `P` key saves the cells of the canvas as an image, one pixel per cell
+pub const GRID_IMAGE_PATH: &str = "game_of_life.png";
+/// Where `F12` saves the frame as it is shown in the window
+pub const SCREENSHOT_PATH: &str = "screenshot.png";
+
+/// What the cells at the edges of the canvas see beyond them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Boundary {
+    /// The canvas wraps around, cells at an edge neighbor the cells at the opposite edge
+    #[default]
+    Wrap,
+    /// The canvas ends at its edges, the cells beyond them are dead
+    Clamp,
+}
 
 pub struct GameOfLifeApp {
     time: Time,
@@ -44,15 +75,35 @@ pub struct GameOfLifeApp {
     camera: camera::OrthographicCamera,
     camera_controller: camera::CameraController,
 
+    rule: dsl::Rule,
+    /// Position of the current rule in the catalog, if it was picked from there
+    catalog_index: Option<usize>,
+    backend: dsl::Backend,
+    boundary: Boundary,
+    /// A pattern to place at an offset once the canvas exists
+    initial_pattern: Option<(Pattern, (u32, u32))>,
+    /// A snapshot to restore once the canvas exists
+    initial_snapshot: Option<Snapshot>,
     data: Option<CanvasData>,
     quad_pipeline: Option<QuadPipeline>,
     init_pipeline: Option<wgpu::ComputePipeline>,
     draw_pipeline: Option<wgpu::ComputePipeline>,
     game_of_life_pipeline: Option<wgpu::ComputePipeline>,
+    gui: Option<gui::Gui>,
+    rule_panel: gui::RulePanel,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    shader_watcher: Option<hot_reload::ShaderWatcher>,
 }
 
 impl Default for GameOfLifeApp {
     fn default() -> Self {
+        Self::new(dsl::rulesets::conways_game_of_life().into())
+    }
+}
+
+impl GameOfLifeApp {
+    /// Create the app with the rule that the update shader will be built from
+    pub fn new(rule: dsl::Rule) -> Self {
         let mut camera = camera::OrthographicCamera::default();
         camera.zoom_to_fit_pixels(SIM_SIZE, SIM_SIZE);
 
@@ -71,16 +122,403 @@ impl Default for GameOfLifeApp {
             camera,
             camera_controller: camera::CameraController::new(CAMERA_MOVE_SPEED),
 
+            rule,
+            catalog_index: None,
+            backend: dsl::Backend::default(),
+            boundary: Boundary::default(),
+            initial_pattern: None,
+            initial_snapshot: None,
             data: None,
             quad_pipeline: None,
             init_pipeline: None,
             draw_pipeline: None,
             game_of_life_pipeline: None,
+            gui: None,
+            rule_panel: gui::RulePanel::default(),
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            shader_watcher: None,
+        }
+    }
+
+    /// Choose how the rule is turned into wgsl, the storage table backend is used by default
+    pub fn with_backend(mut self, backend: dsl::Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Choose what happens at the edges of the canvas, they wrap around by default
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    /// Start with `pattern` placed with its top left corner at `offset` on the canvas
+    pub fn with_pattern(mut self, pattern: Pattern, offset: (u32, u32)) -> Self {
+        self.initial_pattern = Some((pattern, offset));
+        self
+    }
+
+    /// Start from a saved session, the rule, boundary and camera of the snapshot replace the ones
+    /// the app was created with
+    pub fn with_snapshot(mut self, snapshot: Snapshot) -> Self {
+        self.initial_snapshot = Some(snapshot);
+        self
+    }
+
+    pub fn rule(&self) -> &dsl::Rule {
+        &self.rule
+    }
+
+    /// Switch to another rule while running, the cells on the canvas are kept. When the current
+    /// shader reads the rule from the storage table and the new rule has the same neighborhood and
+    /// number of states only the table is rewritten, otherwise only the update pipeline is
+    /// rebuilt. The current rule stays in place if the new one fails to build.
+    pub fn set_rule(
+        &mut self,
+        context: &mut GlassContext,
+        rule: dsl::Rule,
+    ) -> Result<(), ShaderError> {
+        let Some(data) = &self.data else {
+            // Not started yet, the pipelines are built from this rule in `start`
+            self.rule = rule;
+            return Ok(());
+        };
+
+        let uses_table =
+            |rule: &dsl::Rule| self.backend.for_rule(rule) == dsl::Backend::StorageTable;
+        let same_shader = uses_table(&self.rule)
+            && uses_table(&rule)
+            && self.rule.neighborhood == rule.neighborhood
+            && self.rule.states == rule.states;
+
+        if same_shader {
+            shaders::check_rule(&rule)?;
+        } else {
+            self.game_of_life_pipeline = Some(Pipelines::load_update(
+                context,
+                &rule,
+                self.backend,
+                self.boundary,
+            )?);
+        }
+        if let Some(table) = dsl::RuleTable::build(&rule) {
+            data.write_rule_table(context, &table);
+        }
+
+        self.rule = rule;
+        self.catalog_index = None;
+        Ok(())
+    }
+
+    /// Place a pattern with the top left corner of its bounding box at `offset` on the canvas,
+    /// the cells in the bounding box are replaced. Fails if it doesn't fit on the canvas.
+    pub fn place_pattern(
+        &mut self,
+        context: &GlassContext,
+        pattern: &Pattern,
+        offset: (u32, u32),
+    ) -> Result<(), PatternError> {
+        match &self.data {
+            Some(data) => data.write_pattern(context, pattern, offset),
+            None => {
+                self.initial_pattern = Some((pattern.clone(), offset));
+                Ok(())
+            }
+        }
+    }
+
+    /// Read the canvas back from the GPU, the pattern holds its living cells and the rulestring of
+    /// the rule when it came from the catalog
+    pub fn pattern(&self, context: &GlassContext) -> Result<Pattern, wgpu::BufferAsyncError> {
+        let grid = CanvasData::read_cells(context, self.current_cells())?;
+        let mut pattern = Pattern::from_grid(&grid);
+        pattern.rule = self
+            .catalog_index
+            .map(|index| dsl::rulesets::CATALOG[index].1.to_string());
+        Ok(pattern)
+    }
+
+    /// Read the whole session back from the GPU: the states of the cells, the generation, the rule
+    /// and boundary and the camera
+    pub fn snapshot(&self, context: &GlassContext) -> Result<Snapshot, wgpu::BufferAsyncError> {
+        let grid = CanvasData::read_cells(context, self.current_cells())?;
+        let mut snapshot = Snapshot::new(&grid, self.rule.clone());
+        snapshot.generation = self.count as u64;
+        snapshot.catalog_name = self
+            .catalog_index
+            .map(|index| dsl::rulesets::CATALOG[index].0.to_string());
+        snapshot.boundary = self.boundary;
+        snapshot.camera_position = self.camera.position().to_array();
+        snapshot.camera_scale = self.camera.scale();
+        Ok(snapshot)
+    }
+
+    /// Continue a saved session. The pipelines are rebuilt for the rule and boundary of the
+    /// snapshot and every cell of the canvas is replaced. Nothing changes if the rule fails to
+    /// build or the snapshot is of a canvas of another size.
+    pub fn restore(
+        &mut self,
+        context: &mut GlassContext,
+        snapshot: Snapshot,
+    ) -> Result<(), SnapshotError> {
+        if (snapshot.width, snapshot.height) != (SIM_SIZE, SIM_SIZE) {
+            return Err(SnapshotError::CanvasSize {
+                width: snapshot.width,
+                height: snapshot.height,
+            });
+        }
+        let grid = snapshot.grid()?;
+        let Some(data) = &self.data else {
+            // Not started yet, `start` restores it once the canvas exists
+            self.initial_snapshot = Some(snapshot);
+            return Ok(());
+        };
+
+        let (init_pipeline, game_of_life_pipeline) =
+            Pipelines::load_game_of_life(context, &snapshot.rule, self.backend, snapshot.boundary)?;
+        if let Some(table) = dsl::RuleTable::build(&snapshot.rule) {
+            data.write_rule_table(context, &table);
+        }
+        data.write_cells(context, &grid)?;
+
+        self.init_pipeline = Some(init_pipeline);
+        self.game_of_life_pipeline = Some(game_of_life_pipeline);
+        self.rule = snapshot.rule;
+        self.catalog_index = snapshot.catalog_name.and_then(|name| {
+            dsl::rulesets::CATALOG
+                .iter()
+                .position(|(catalog_name, _)| *catalog_name == name)
+        });
+        self.boundary = snapshot.boundary;
+        self.count = snapshot.generation as usize;
+        self.camera
+            .set_position(Vec2::from_array(snapshot.camera_position));
+        self.camera.set_scale(snapshot.camera_scale);
+
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        if let (Some(watcher), Ok(shader_files)) = (
+            &mut self.shader_watcher,
+            Pipelines::shader_files(self.boundary),
+        ) {
+            watcher.set_shader_files(shader_files);
+        }
+        Ok(())
+    }
+
+    /// Save the cells of the canvas to `GRID_IMAGE_PATH`
+    fn export_grid_image(&self, context: &GlassContext) {
+        let saved = CanvasData::read_cells(context, self.current_cells())
+            .map_err(PictureError::from)
+            .and_then(|grid| picture::save_grid_png(&grid, self.rule.states, GRID_IMAGE_PATH));
+        match saved {
+            Ok(()) => log::info!("Exported the canvas to {GRID_IMAGE_PATH}"),
+            Err(err) => log::error!("Failed to export to {GRID_IMAGE_PATH}: {err}"),
+        }
+    }
+
+    /// Render the canvas through the camera like a frame of the window and read it back
+    pub fn capture_frame(&self, context: &GlassContext) -> Result<image::RgbaImage, PictureError> {
+        let size = context.primary_render_window().window().inner_size();
+        let format = GlassWindow::surface_format();
+        let texture = context.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("Screenshot"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let mut encoder =
+            context
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Screenshot"),
+                });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        draw_canvas(self, &mut encoder, &view);
+        context.queue().submit(Some(encoder.finish()));
+
+        let size = (size.width, size.height);
+        let bytes = canvas_data::read_texture(context, &texture, size, 4)?;
+        picture::rgba_image(bytes, size, format)
+    }
+
+    /// Save the frame to `SCREENSHOT_PATH`
+    fn save_screenshot(&self, context: &GlassContext) {
+        let saved = self
+            .capture_frame(context)
+            .and_then(|frame| frame.save(SCREENSHOT_PATH).map_err(PictureError::from));
+        match saved {
+            Ok(()) => log::info!("Saved a screenshot to {SCREENSHOT_PATH}"),
+            Err(err) => log::error!("Failed to save a screenshot to {SCREENSHOT_PATH}: {err}"),
+        }
+    }
+
+    /// Save the session to `SNAPSHOT_PATH`
+    fn save_snapshot(&self, context: &GlassContext) {
+        let saved = self
+            .snapshot(context)
+            .map_err(SnapshotError::from)
+            .and_then(|snapshot| snapshot.save(SNAPSHOT_PATH));
+        match saved {
+            Ok(()) => log::info!("Saved generation {} to {SNAPSHOT_PATH}", self.count),
+            Err(err) => log::error!("Failed to save to {SNAPSHOT_PATH}: {err}"),
+        }
+    }
+
+    /// Continue the session saved in `SNAPSHOT_PATH`
+    fn load_snapshot(&mut self, context: &mut GlassContext) {
+        let restored =
+            Snapshot::load(SNAPSHOT_PATH).and_then(|snapshot| self.restore(context, snapshot));
+        match restored {
+            Ok(()) => log::info!("Loaded generation {} from {SNAPSHOT_PATH}", self.count),
+            Err(err) => log::error!("Failed to load {SNAPSHOT_PATH}: {err}"),
+        }
+    }
+
+    /// The texture the next update reads the cells from, it holds the latest generation
+    fn current_cells(&self) -> &Texture {
+        let data = self.data.as_ref().unwrap();
+        if self.count % 2 == 0 {
+            &data.data_in
+        } else {
+            &data.canvas
+        }
+    }
+
+    /// Save the living cells of the canvas to `EXPORT_PATH`, trimmed to their bounding box
+    fn export_pattern(&self, context: &GlassContext) {
+        let pattern = match self.pattern(context) {
+            Ok(pattern) => pattern.trim(),
+            Err(err) => {
+                log::error!("Failed to read the canvas: {err}");
+                return;
+            }
+        };
+        match pattern::write_pattern(EXPORT_PATH, &pattern) {
+            Ok(()) => log::info!("Exported {} cells to {EXPORT_PATH}", pattern.cells.len()),
+            Err(err) => log::error!("Failed to export to {EXPORT_PATH}: {err}"),
+        }
+    }
+
+    /// Switch to the rule `step` places further along in the catalog, wrapping around at the ends
+    fn cycle_catalog(&mut self, context: &mut GlassContext, step: isize) {
+        let len = dsl::rulesets::CATALOG.len() as isize;
+        let index = match self.catalog_index {
+            Some(index) => (index as isize + step).rem_euclid(len) as usize,
+            None => 0,
+        };
+        self.pick_rule(context, gui::RuleChoice::Catalog(index));
+    }
+
+    /// Switch to a rule of the catalog or the library or one the user typed in. Why it failed to
+    /// load or build is shown in the rule panel until another rule is picked.
+    fn pick_rule(&mut self, context: &mut GlassContext, choice: gui::RuleChoice) {
+        let catalog_index = match choice {
+            gui::RuleChoice::Catalog(index) => Some(index),
+            _ => None,
+        };
+        let (name, rule) = match choice {
+            gui::RuleChoice::Catalog(index) => {
+                let (name, rulestring) = dsl::rulesets::CATALOG[index];
+                let rule =
+                    dsl::parse_rulestring(rulestring).expect("catalog rulestrings are valid");
+                (name.to_string(), Ok(rule))
+            }
+            gui::RuleChoice::Library(name) => {
+                let rule = dsl::load_rule(&name);
+                (name, rule)
+            }
+            gui::RuleChoice::Typed(name) => {
+                let rule = dsl::find_rule(&name);
+                (name, rule)
+            }
+        };
+
+        let switched = rule.map_err(|err| err.to_string()).and_then(|rule| {
+            match self.set_rule(context, rule.clone()) {
+                Ok(()) => Ok(rule),
+                Err(err) => Err(err.to_string()),
+            }
+        });
+        match switched {
+            Ok(rule) => {
+                log::info!("Switched to {name}");
+                self.rule_panel.error = None;
+                // `set_rule` forgets where the previous rule came from
+                self.catalog_index = catalog_index;
+                if catalog_index.is_none() {
+                    self.rule_panel.picked = Some((name.clone(), rule));
+                }
+                context
+                    .primary_render_window()
+                    .window()
+                    .set_title(&format!("Game Of Life: {name}"));
+            }
+            Err(err) => {
+                log::error!("Failed to switch to {name}: {err}");
+                self.rule_panel.error = Some(format!("{name}: {err}"));
+            }
+        }
+    }
+
+    /// Rebuild the pipelines of the shaders composed from a file that changed on disk. A pipeline
+    /// whose new shader fails to build keeps running the old one and the error is shown over the
+    /// canvas until a later change builds.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    fn reload_shaders(&mut self, context: &mut GlassContext) {
+        let Some(watcher) = &self.shader_watcher else {
+            return;
+        };
+        let shaders = watcher.changed_shaders();
+        if shaders.is_empty() {
+            return;
+        }
+
+        let mut results = Vec::new();
+        for shader in shaders {
+            let reloaded = match shader {
+                pipelines::DRAW_SHADER => Pipelines::load_draw(context).map(|draw_pipeline| {
+                    self.draw_pipeline = Some(draw_pipeline);
+                }),
+                _ => Pipelines::load_game_of_life(context, &self.rule, self.backend, self.boundary)
+                    .map(|(init_pipeline, game_of_life_pipeline)| {
+                        self.init_pipeline = Some(init_pipeline);
+                        self.game_of_life_pipeline = Some(game_of_life_pipeline);
+                    }),
+            };
+            match reloaded {
+                Ok(()) => {
+                    log::info!("Reloaded {shader}");
+                    results.push((shader, None));
+                }
+                Err(err) => {
+                    log::error!("Failed to reload {shader}, keeping the old pipeline:\n{err}");
+                    results.push((shader, Some(err.summary())));
+                }
+            }
+        }
+
+        let watcher = self.shader_watcher.as_mut().unwrap();
+        // Follow imports that were added or removed
+        if let Ok(shader_files) = Pipelines::shader_files(self.boundary) {
+            watcher.set_shader_files(shader_files);
+        }
+        for (shader, error) in results {
+            match error {
+                Some(error) => watcher.errors.insert(shader, error),
+                None => watcher.errors.remove(shader),
+            };
         }
     }
-}
 
-impl GameOfLifeApp {
     fn world_pos_to_canvas_pos(&self, world_pos: Vec2) -> Vec2 {
         world_pos + Vec2::new(SIM_SIZE as f32 / 2.0, SIM_SIZE as f32 / 2.0)
     }
@@ -105,17 +543,19 @@ impl GameOfLifeApp {
 // - Update is run every frame
 // - Render is run for each window after update every frame
 impl GlassApp for GameOfLifeApp {
-    fn start(
-        &mut self,
-        _event_loop: &winit::event_loop::EventLoop<()>,
-        context: &mut GlassContext,
-    ) {
+    fn start(&mut self, event_loop: &winit::event_loop::EventLoop<()>, context: &mut GlassContext) {
         // Create pipelines
         let Pipelines {
             init_pipeline,
             game_of_life_pipeline,
             draw_pipeline,
-        } = Pipelines::load(context);
+        } = Pipelines::load(context, &self.rule, self.backend, self.boundary).unwrap_or_else(
+            |err| {
+                log::error!("Failed to build rule, falling back to Conway's game of life: {err}");
+                self.rule = dsl::rulesets::conways_game_of_life().into();
+                Pipelines::load(context, &self.rule, self.backend, self.boundary).unwrap()
+            },
+        );
 
         let quad_pipeline = QuadPipeline::new(context.device(), GlassWindow::surface_format());
         self.data = Some(CanvasData::create(
@@ -124,34 +564,90 @@ impl GlassApp for GameOfLifeApp {
             &init_pipeline,
             &draw_pipeline,
         ));
+        if let Some(table) = dsl::RuleTable::build(&self.rule) {
+            self.data
+                .as_ref()
+                .unwrap()
+                .write_rule_table(context, &table);
+        }
 
         self.quad_pipeline = Some(quad_pipeline);
         self.init_pipeline = Some(init_pipeline);
         self.draw_pipeline = Some(draw_pipeline);
         self.game_of_life_pipeline = Some(game_of_life_pipeline);
 
+        self.gui = Some(gui::Gui::new(
+            event_loop,
+            context.device(),
+            context.primary_render_window().window(),
+        ));
+        let library = dsl::library_rules(&*shaders::default_source()).unwrap_or_else(|err| {
+            log::error!("Failed to list the rule library: {err}");
+            Vec::new()
+        });
+        self.rule_panel = gui::RulePanel::new(library);
+
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        {
+            self.shader_watcher = match Pipelines::shader_files(self.boundary) {
+                Ok(shader_files) => hot_reload::ShaderWatcher::new(shader_files)
+                    .map_err(|err| log::error!("Failed to watch shader files: {err}"))
+                    .ok(),
+                Err(err) => {
+                    log::error!("Failed to find the files of the shaders to watch: {err}");
+                    None
+                }
+            };
+        }
+
         init_game_of_life(self, context);
+        if let Some((pattern, offset)) = self.initial_pattern.take() {
+            if let Err(err) = self
+                .data
+                .as_ref()
+                .unwrap()
+                .write_pattern(context, &pattern, offset)
+            {
+                log::error!("Failed to place the pattern: {err}");
+            }
+        }
+        if let Some(snapshot) = self.initial_snapshot.take() {
+            if let Err(err) = self.restore(context, snapshot) {
+                log::error!("Failed to restore the snapshot: {err}");
+            }
+        }
     }
 
     fn input(
         &mut self,
-        _context: &mut GlassContext,
+        context: &mut GlassContext,
         _event_loop: &winit::event_loop::EventLoopWindowTarget<()>,
         event: &winit::event::Event<()>,
     ) {
-        handle_inputs(self, event);
+        // Clicks and keys meant for the ui don't reach the canvas
+        if let (Some(gui), winit::event::Event::WindowEvent { event, .. }) = (&mut self.gui, event)
+        {
+            if gui.on_event(event) {
+                return;
+            }
+        }
+        handle_inputs(self, context, event);
     }
 
     fn update(&mut self, context: &mut GlassContext) {
         run_update(self, context);
     }
 
-    fn render(&mut self, _context: &GlassContext, render_data: RenderData) {
-        render(self, render_data);
+    fn render(&mut self, context: &GlassContext, render_data: RenderData) {
+        render(self, context, render_data);
     }
 }
 
-fn handle_inputs(app: &mut GameOfLifeApp, event: &winit::event::Event<()>) {
+fn handle_inputs(
+    app: &mut GameOfLifeApp,
+    context: &mut GlassContext,
+    event: &winit::event::Event<()>,
+) {
     if let winit::event::Event::WindowEvent { event, .. } = event {
         app.camera_controller.process_events(event);
 
@@ -166,6 +662,27 @@ fn handle_inputs(app: &mut GameOfLifeApp, event: &winit::event::Event<()>) {
             } => {
                 app.drawing = state == &winit::event::ElementState::Pressed;
             }
+            // Cycle through the catalog of rules with `[` and `]`, export the canvas with `E` or as
+            // an image with `P`, save the session with `F5` and load it with `F9` and take a
+            // screenshot with `F12`
+            winit::event::WindowEvent::KeyboardInput {
+                input:
+                    winit::event::KeyboardInput {
+                        state: winit::event::ElementState::Pressed,
+                        virtual_keycode: Some(keycode),
+                        ..
+                    },
+                ..
+            } => match keycode {
+                winit::event::VirtualKeyCode::LBracket => app.cycle_catalog(context, -1),
+                winit::event::VirtualKeyCode::RBracket => app.cycle_catalog(context, 1),
+                winit::event::VirtualKeyCode::E => app.export_pattern(context),
+                winit::event::VirtualKeyCode::P => app.export_grid_image(context),
+                winit::event::VirtualKeyCode::F12 => app.save_screenshot(context),
+                winit::event::VirtualKeyCode::F5 => app.save_snapshot(context),
+                winit::event::VirtualKeyCode::F9 => app.load_snapshot(context),
+                _ => (),
+            },
             winit::event::WindowEvent::MouseWheel { delta, .. } => {
                 let mut x_scroll_diff = 0.0;
                 let mut y_scroll_diff = 0.0;
@@ -199,21 +716,31 @@ fn handle_inputs(app: &mut GameOfLifeApp, event: &winit::event::Event<()>) {
 
 fn run_update(app: &mut GameOfLifeApp, context: &mut GlassContext) {
     app.time.update();
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    app.reload_shaders(context);
 
     let now = Instant::now();
     app.dt_sum += (now - app.current_time).as_secs_f32();
     app.num_dts += 1.0;
     if app.num_dts == 1000.0 {
-        // Set fps
-        context.primary_render_window().window().set_title(&format!(
-            "Game Of Life: {:.2}",
-            1.0 / (app.dt_sum / app.num_dts)
-        ));
+        // Set fps, and the name of the rule if it came from the catalog
+        let fps = 1.0 / (app.dt_sum / app.num_dts);
+        let title = match app.catalog_index {
+            Some(index) => format!(
+                "Game Of Life: {} {:.2}",
+                dsl::rulesets::CATALOG[index].0,
+                fps
+            ),
+            None => format!("Game Of Life: {:.2}", fps),
+        };
+        context.primary_render_window().window().set_title(&title);
         app.num_dts = 0.0;
         app.dt_sum = 0.0;
     }
     app.current_time = Instant::now();
 
+    run_gui(app, context);
+
     // Use only single command queue
     let mut encoder = context
         .device()
@@ -244,7 +771,43 @@ fn run_update(app: &mut GameOfLifeApp, context: &mut GlassContext) {
     context.queue().submit(Some(encoder.finish()));
 }
 
-fn render(app: &mut GameOfLifeApp, render_data: RenderData) {
+/// Run the ui and switch to the rule picked in the rule panel
+fn run_gui(app: &mut GameOfLifeApp, context: &mut GlassContext) {
+    let Some(gui) = &mut app.gui else {
+        return;
+    };
+
+    let mut choice = None;
+    gui.run(context.primary_render_window().window(), |ui| {
+        choice = app.rule_panel.show(ui, &app.rule, app.catalog_index);
+
+        // Keep showing why the shaders failed to reload until they are fixed
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        if let Some(watcher) = &app.shader_watcher {
+            gui::show_shader_errors(ui, &watcher.error_messages());
+        }
+    });
+    if let Some(choice) = choice {
+        app.pick_rule(context, choice);
+    }
+}
+
+fn render(app: &mut GameOfLifeApp, context: &GlassContext, render_data: RenderData) {
+    let RenderData { encoder, frame, .. } = render_data;
+
+    let view = frame
+        .texture
+        .create_view(&wgpu::TextureViewDescriptor::default());
+    draw_canvas(app, encoder, &view);
+
+    if let Some(gui) = &mut app.gui {
+        let size = PhysicalSize::new(frame.texture.width(), frame.texture.height());
+        gui.render(context.device(), context.queue(), encoder, &view, size);
+    }
+}
+
+/// Draw the canvas through the camera into `view`, a frame of the window or a screenshot
+fn draw_canvas(app: &GameOfLifeApp, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
     let GameOfLifeApp {
         data,
         camera,
@@ -254,18 +817,13 @@ fn render(app: &mut GameOfLifeApp, render_data: RenderData) {
 
     let canvas_data = data.as_ref().unwrap();
     let quad_pipeline = quad_pipeline.as_ref().unwrap();
-    let RenderData { encoder, frame, .. } = render_data;
-
-    let view = frame
-        .texture
-        .create_view(&wgpu::TextureViewDescriptor::default());
 
     {
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
             depth_stencil_attachment: None,
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
+                view,
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
@@ -343,6 +901,10 @@ fn update_game_of_life(
                     binding: 1,
                     resource: wgpu::BindingResource::TextureView(data_in),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: data.rule_table.as_entire_binding(),
+                },
             ],
         });
 
@@ -1,4 +1,4 @@
-use game_of_life_sim::SIM_SIZE;
+use game_of_life_sim::{dsl, pattern, picture, snapshot::Snapshot, GameOfLifeApp, SIM_SIZE};
 use glass::{device_context::DeviceConfig, wgpu, window::WindowConfig, Glass, GlassConfig};
 
 fn config() -> GlassConfig {
@@ -27,6 +27,60 @@ fn config() -> GlassConfig {
     }
 }
 
+/// Pick the rule from the first argument, which can be the name of a rule in
+/// [`dsl::rulesets::CATALOG`] or in the `assets/rules` library, a rulestring such as `B36/S23` or
+/// the path to a rule file. Falls back to Conway's game of life when no argument is given.
+fn rule() -> dsl::Rule {
+    let Some(path) = std::env::args().nth(1) else {
+        return dsl::rulesets::conways_game_of_life().into();
+    };
+
+    let rule = dsl::find_rule(&path).unwrap_or_else(|err| {
+        eprintln!("{path}:{err}");
+        std::process::exit(1);
+    });
+    if let Err(errors) = rule.check() {
+        for err in errors {
+            eprintln!("{path}: {err}");
+        }
+        std::process::exit(1);
+    }
+
+    rule
+}
+
+/// Read the pattern file given as the second argument, it is placed in the center of the canvas.
+/// A `.png` image is read with a living cell for every bright pixel and a `.snapshot` file
+/// continues the saved session instead, with its own rule.
+fn with_pattern(app: GameOfLifeApp) -> GameOfLifeApp {
+    let Some(path) = std::env::args().nth(2) else {
+        return app;
+    };
+    if path.ends_with(".snapshot") {
+        let snapshot = Snapshot::load(&path).unwrap_or_else(|err| {
+            eprintln!("{path}: {err}");
+            std::process::exit(1);
+        });
+        return app.with_snapshot(snapshot);
+    }
+
+    let pattern = if path.ends_with(".png") {
+        picture::read_image_pattern(&path, picture::DEFAULT_THRESHOLD)
+            .map_err(|err| err.to_string())
+    } else {
+        pattern::read_pattern(&path).map_err(|err| err.to_string())
+    };
+    let pattern = pattern.unwrap_or_else(|err| {
+        eprintln!("{path}: {err}");
+        std::process::exit(1);
+    });
+    let offset = (
+        SIM_SIZE.saturating_sub(pattern.width) / 2,
+        SIM_SIZE.saturating_sub(pattern.height) / 2,
+    );
+    app.with_pattern(pattern, offset)
+}
+
 fn main() -> std::result::Result<(), glass::GlassError> {
-    Glass::new(game_of_life_sim::GameOfLifeApp::default(), config()).run()
+    Glass::new(with_pattern(GameOfLifeApp::new(rule())), config()).run()
 }
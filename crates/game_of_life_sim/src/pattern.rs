@@ -0,0 +1,393 @@
+pub mod life105;
+pub mod life106;
+pub mod macrocell;
+pub mod plaintext;
+pub mod quadtree;
+pub mod rle;
+
+use std::{collections::BTreeSet, fmt, path::Path};
+
+use crate::{cpu::Grid, SIM_SIZE};
+
+/// A pattern of living cells as stored in pattern files, placed on the canvas or saved from it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pattern {
+    /// Size of the bounding box, every cell lies in `0..width` and `0..height`
+    pub width: u32,
+    pub height: u32,
+    /// The living cells as (x, y) from the top left corner of the bounding box, y grows down
+    pub cells: BTreeSet<(u32, u32)>,
+    pub name: Option<String>,
+    /// The rule the pattern is meant to run under as a rulestring, e.g. `B3/S23`
+    pub rule: Option<String>,
+    pub comments: Vec<String>,
+}
+
+impl Pattern {
+    /// An empty pattern with a bounding box of `width` by `height`
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            ..Self::default()
+        }
+    }
+
+    /// A pattern of the cells at any coordinates, moved so its bounding box starts at (0, 0).
+    /// Fails if the cells spread further than the canvas.
+    pub fn from_cells(cells: impl IntoIterator<Item = (i64, i64)>) -> Result<Self, PatternError> {
+        let cells = cells.into_iter().collect::<Vec<_>>();
+        let (Some(min_x), Some(max_x)) = (
+            cells.iter().map(|(x, _)| *x).min(),
+            cells.iter().map(|(x, _)| *x).max(),
+        ) else {
+            return Ok(Self::default());
+        };
+        let min_y = cells.iter().map(|(_, y)| *y).min().unwrap_or(0);
+        let max_y = cells.iter().map(|(_, y)| *y).max().unwrap_or(0);
+
+        // The span of two i64 doesn't always fit in one
+        let span =
+            |min: i64, max: i64| u64::try_from(max as i128 - min as i128 + 1).unwrap_or(u64::MAX);
+        let (width, height) = (span(min_x, max_x), span(min_y, max_y));
+        if width > SIM_SIZE as u64 || height > SIM_SIZE as u64 {
+            return Err(PatternError::TooLarge {
+                width,
+                height,
+                offset: (0, 0),
+                canvas: (SIM_SIZE, SIM_SIZE),
+            });
+        }
+
+        Ok(Self {
+            width: width as u32,
+            height: height as u32,
+            cells: cells
+                .into_iter()
+                .map(|(x, y)| ((x - min_x) as u32, (y - min_y) as u32))
+                .collect(),
+            ..Self::default()
+        })
+    }
+
+    /// The living cells of a grid, the bounding box is the whole grid
+    pub fn from_grid(grid: &Grid) -> Self {
+        let mut pattern = Self::new(grid.width(), grid.height());
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                if grid.is_alive(x, y) {
+                    pattern.cells.insert((x, y));
+                }
+            }
+        }
+        pattern
+    }
+
+    /// A grid the size of the bounding box with the cells of the pattern alive
+    pub fn to_grid(&self) -> Grid {
+        let mut grid = Grid::new(self.width, self.height);
+        for &(x, y) in &self.cells {
+            grid.set(x, y, 1);
+        }
+        grid
+    }
+
+    pub fn is_alive(&self, x: u32, y: u32) -> bool {
+        self.cells.contains(&(x, y))
+    }
+
+    /// Set a cell alive, growing the bounding box to hold it
+    pub fn insert(&mut self, x: u32, y: u32) {
+        self.width = self.width.max(x + 1);
+        self.height = self.height.max(y + 1);
+        self.cells.insert((x, y));
+    }
+
+    /// Shrink the bounding box to the living cells, moving them to start at (0, 0)
+    pub fn trim(&self) -> Self {
+        let min_x = self.cells.iter().map(|(x, _)| *x).min().unwrap_or(0);
+        let min_y = self.cells.iter().map(|(_, y)| *y).min().unwrap_or(0);
+        let cells = self
+            .cells
+            .iter()
+            .map(|&(x, y)| (x - min_x, y - min_y))
+            .collect::<BTreeSet<_>>();
+
+        Self {
+            width: cells.iter().map(|(x, _)| x + 1).max().unwrap_or(0),
+            height: cells.iter().map(|(_, y)| y + 1).max().unwrap_or(0),
+            cells,
+            name: self.name.clone(),
+            rule: self.rule.clone(),
+            comments: self.comments.clone(),
+        }
+    }
+}
+
+/// An error produced while reading, writing or placing a pattern
+#[derive(Debug)]
+pub enum PatternError {
+    Io(std::io::Error),
+    /// A pattern file that doesn't follow its format, the line is counted from 1
+    Parse {
+        line: usize,
+        message: String,
+    },
+    UnknownFormat(String),
+    /// A pattern that doesn't fit on the canvas at the offset it is placed at, Macrocell patterns
+    /// can be far larger than a `u32`
+    TooLarge {
+        width: u64,
+        height: u64,
+        offset: (u32, u32),
+        canvas: (u32, u32),
+    },
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Io(err) => write!(f, "{err}"),
+            PatternError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            PatternError::UnknownFormat(path) => write!(f, "{path} is not a known pattern format"),
+            PatternError::TooLarge {
+                width,
+                height,
+                offset,
+                canvas,
+            } => write!(
+                f,
+                "a {width}x{height} pattern at {},{} does not fit on the {}x{} canvas",
+                offset.0, offset.1, canvas.0, canvas.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl From<std::io::Error> for PatternError {
+    fn from(err: std::io::Error) -> Self {
+        PatternError::Io(err)
+    }
+}
+
+/// The pattern file formats that can be read and written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Run Length Encoded, `.rle`
+    Rle,
+    /// Rows of `.` and `O`, `.cells`
+    Plaintext,
+    /// Blocks of rows of `.` and `*`, `.lif` or `.life` starting with `#Life 1.05`
+    Life105,
+    /// Coordinates of the living cells, `.lif` or `.life` starting with `#Life 1.06`
+    Life106,
+    /// A quadtree of the pattern where equal squares are written once, `.mc`
+    Macrocell,
+}
+
+impl Format {
+    /// The format of a file by its extension. Both Life formats use the same extensions, files are
+    /// written as Life 1.06 which holds any pattern in the least space.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "rle" => Some(Format::Rle),
+            "cells" => Some(Format::Plaintext),
+            "lif" | "life" => Some(Format::Life106),
+            "mc" => Some(Format::Macrocell),
+            _ => None,
+        }
+    }
+
+    /// The format of a pattern by the way its contents start
+    pub fn detect(source: &str) -> Option<Self> {
+        let mut lines = source
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty());
+        let first = lines.clone().next()?;
+        if first.starts_with(life105::HEADER) {
+            return Some(Format::Life105);
+        }
+        if first.starts_with(life106::HEADER) {
+            return Some(Format::Life106);
+        }
+        if first.starts_with(macrocell::HEADER) {
+            return Some(Format::Macrocell);
+        }
+        if first.starts_with('!') {
+            return Some(Format::Plaintext);
+        }
+
+        let first = lines.find(|line| !line.starts_with('#'))?;
+        let is_rle_header = first
+            .split_once('=')
+            .is_some_and(|(key, _)| key.trim() == "x");
+        if is_rle_header {
+            Some(Format::Rle)
+        } else if first.chars().all(|c| matches!(c, '.' | 'O')) {
+            Some(Format::Plaintext)
+        } else {
+            None
+        }
+    }
+
+    pub fn parse(self, source: &str) -> Result<Pattern, PatternError> {
+        match self {
+            Format::Rle => rle::parse(source),
+            Format::Plaintext => plaintext::parse(source),
+            Format::Life105 => life105::parse(source),
+            Format::Life106 => life106::parse(source),
+            Format::Macrocell => macrocell::parse(source),
+        }
+    }
+
+    pub fn write(self, pattern: &Pattern) -> String {
+        match self {
+            Format::Rle => rle::write(pattern),
+            Format::Plaintext => plaintext::write(pattern),
+            Format::Life105 => life105::write(pattern),
+            Format::Life106 => life106::write(pattern),
+            Format::Macrocell => macrocell::write(pattern),
+        }
+    }
+}
+
+/// Parse a pattern in any format, detected from its contents
+pub fn parse_pattern(source: &str) -> Result<Pattern, PatternError> {
+    let format = Format::detect(source).ok_or_else(|| PatternError::Parse {
+        line: 1,
+        message: "not a pattern in any known format".to_string(),
+    })?;
+    format.parse(source)
+}
+
+/// Read a pattern file, the format is detected from the contents of the file and falls back to
+/// its extension
+pub fn read_pattern(path: impl AsRef<Path>) -> Result<Pattern, PatternError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path)?;
+    let format = match Format::detect(&source) {
+        Some(format) => format,
+        None => format(path)?,
+    };
+    format.parse(&source)
+}
+
+/// Write a pattern file, the format is picked by the extension of the file
+pub fn write_pattern(path: impl AsRef<Path>, pattern: &Pattern) -> Result<(), PatternError> {
+    let path = path.as_ref();
+    let contents = format(path)?.write(pattern);
+    Ok(std::fs::write(path, contents)?)
+}
+
+fn format(path: &Path) -> Result<Format, PatternError> {
+    Format::from_path(path).ok_or_else(|| PatternError::UnknownFormat(path.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glider() -> Pattern {
+        let mut glider = Pattern::new(3, 3);
+        glider
+            .cells
+            .extend([(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        glider
+    }
+
+    #[test]
+    fn cells_spread_past_the_canvas_are_too_large() {
+        let pattern = Pattern::from_cells([(-5, 7), (-3, 9)]).unwrap();
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        assert_eq!(pattern.cells, [(0, 0), (2, 2)].into_iter().collect());
+
+        let edge = SIM_SIZE as i64 - 1;
+        let pattern = Pattern::from_cells([(0, 0), (edge, edge)]).unwrap();
+        assert_eq!((pattern.width, pattern.height), (SIM_SIZE, SIM_SIZE));
+
+        let spans = [
+            ((0, 0), (SIM_SIZE as i64, 0), SIM_SIZE as u64 + 1),
+            ((0, 0), (4294967295, 0), 4294967296),
+            ((0, 0), (4294967296, 0), 4294967297),
+            ((i64::MIN, 0), (i64::MAX, 0), u64::MAX),
+        ];
+        for (first, second, span) in spans {
+            let err = Pattern::from_cells([first, second]).unwrap_err();
+            assert!(
+                matches!(err, PatternError::TooLarge { width, height: 1, .. } if width == span),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn formats_are_detected_by_content() {
+        let formats = [
+            Format::Rle,
+            Format::Plaintext,
+            Format::Life105,
+            Format::Life106,
+            Format::Macrocell,
+        ];
+        for format in formats {
+            let written = format.write(&glider());
+            assert_eq!(Format::detect(&written), Some(format), "{written}");
+            assert_eq!(parse_pattern(&written).unwrap().cells, glider().cells);
+        }
+
+        assert_eq!(
+            Format::detect("#C comment\nx = 3, y = 1\n3o!"),
+            Some(Format::Rle)
+        );
+        assert_eq!(Format::detect("\n.O.\nOOO\n"), Some(Format::Plaintext));
+        assert_eq!(
+            Format::detect("!Name: Blinker\nOOO"),
+            Some(Format::Plaintext)
+        );
+        assert_eq!(Format::detect("hello"), None);
+        assert_eq!(Format::detect(""), None);
+    }
+
+    #[test]
+    fn formats_are_picked_by_extension() {
+        let format = |path: &str| Format::from_path(Path::new(path));
+        assert_eq!(format("glider.rle"), Some(Format::Rle));
+        assert_eq!(format("glider.RLE"), Some(Format::Rle));
+        assert_eq!(format("glider.cells"), Some(Format::Plaintext));
+        assert_eq!(format("glider.mc"), Some(Format::Macrocell));
+        // Both Life formats share their extensions, they are written as Life 1.06
+        assert_eq!(format("glider.lif"), Some(Format::Life106));
+        assert_eq!(format("glider.life"), Some(Format::Life106));
+        assert_eq!(format("glider.txt"), None);
+        assert_eq!(format("glider"), None);
+    }
+
+    #[test]
+    fn lif_files_are_read_by_their_header() {
+        let dir = std::env::temp_dir().join(format!("game_of_life_lif_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut glider = glider();
+        glider.rule = Some("B36/S23".to_string());
+        for format in [Format::Life105, Format::Life106] {
+            let path = dir.join(format!("{format:?}.lif"));
+            std::fs::write(&path, format.write(&glider)).unwrap();
+            let pattern = read_pattern(&path).unwrap();
+            assert_eq!(pattern.cells, glider.cells);
+            // Only Life 1.05 has room for the rule
+            let rule = (format == Format::Life105).then(|| "B36/S23".to_string());
+            assert_eq!(pattern.rule, rule, "{format:?}");
+        }
+
+        // Contents in no known format fall back to the extension
+        let path = dir.join("unknown.lif");
+        std::fs::write(&path, "1 0\n2 1\n").unwrap();
+        assert_eq!(read_pattern(&path).unwrap().cells.len(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+use super::{Pattern, PatternError};
+
+/// The first line of a Life 1.05 file
+pub const HEADER: &str = "#Life 1.05";
+
+/// Parse a Life 1.05 pattern: `#D` descriptions, the rule as `#N` for Conway's game of life or
+/// `#R` with survival and birth counts like `23/3`, and blocks of rows of `*` and `.` each placed
+/// at the position of the `#P x y` line before it
+pub fn parse(source: &str) -> Result<Pattern, PatternError> {
+    let mut cells = Vec::new();
+    let mut name = None;
+    let mut rule = None;
+    let mut comments = Vec::new();
+    // Where the next row of the current block starts
+    let mut position = None::<(i64, i64)>;
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| PatternError::Parse {
+            line: number + 1,
+            message,
+        };
+        let line = line.trim();
+
+        if let Some(directive) = line.strip_prefix('#') {
+            let mut chars = directive.chars();
+            let kind = chars.next();
+            let argument = chars.as_str().trim();
+            match kind {
+                Some('D' | 'C') => comments.push(argument.to_string()),
+                Some('N') if argument.is_empty() => rule = Some("B3/S23".to_string()),
+                Some('N') => name = Some(argument.to_string()),
+                Some('R') => rule = Some(to_rulestring(argument).map_err(error)?),
+                Some('P') => {
+                    let coordinates = argument
+                        .split_whitespace()
+                        .map(|n| n.parse::<i64>())
+                        .collect::<Result<Vec<_>, _>>();
+                    match coordinates.as_deref() {
+                        Ok(&[x, y]) => position = Some((x, y)),
+                        _ => {
+                            return Err(error(format!("expected `#P x y`, found `#P {argument}`")))
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let Some((x, y)) = position.as_mut() else {
+            return Err(error(
+                "expected a `#P x y` line before the cells".to_string(),
+            ));
+        };
+        let too_large = || error("the block is too far from the origin".to_string());
+        for (dx, c) in line.chars().enumerate() {
+            match c {
+                '*' | 'O' => cells.push((x.checked_add(dx as i64).ok_or_else(too_large)?, *y)),
+                '.' => {}
+                c => return Err(error(format!("unexpected character `{c}`"))),
+            }
+        }
+        *y = y.checked_add(1).ok_or_else(too_large)?;
+    }
+
+    Ok(Pattern {
+        name,
+        rule,
+        comments,
+        ..Pattern::from_cells(cells)?
+    })
+}
+
+/// Write a pattern in Life 1.05 format as a single block centered on the origin. Rules that are
+/// not plain birth and survival counts can't be written and are left out, and the bounding box
+/// shrinks to the living cells when it is read back.
+pub fn write(pattern: &Pattern) -> String {
+    let mut output = format!("{HEADER}\n");
+    if let Some(name) = &pattern.name {
+        output.push_str(&format!("#N {name}\n"));
+    }
+    for comment in &pattern.comments {
+        output.push_str(&format!("#D {comment}\n"));
+    }
+    match pattern.rule.as_deref().map(from_rulestring) {
+        Some(Some(rule)) if rule == "23/3" => output.push_str("#N\n"),
+        Some(Some(rule)) => output.push_str(&format!("#R {rule}\n")),
+        _ => {}
+    }
+
+    output.push_str(&format!(
+        "#P {} {}\n",
+        -(pattern.width as i64 / 2),
+        -(pattern.height as i64 / 2)
+    ));
+    for y in 0..pattern.height {
+        let row = (0..pattern.width)
+            .map(|x| if pattern.is_alive(x, y) { '*' } else { '.' })
+            .collect::<String>();
+        output.push_str(&row);
+        output.push('\n');
+    }
+    output
+}
+
+/// `23/3` to `B3/S23`
+fn to_rulestring(rule: &str) -> Result<String, String> {
+    let (survival, birth) = rule
+        .split_once('/')
+        .filter(|(survival, birth)| {
+            (survival.chars().chain(birth.chars())).all(|c| c.is_ascii_digit())
+        })
+        .ok_or_else(|| format!("expected a rule like `23/3`, found `{rule}`"))?;
+    Ok(format!("B{birth}/S{survival}"))
+}
+
+/// `B3/S23` to `23/3`, if the rule is only birth and survival counts
+fn from_rulestring(rule: &str) -> Option<String> {
+    let (birth, survival) = rule.split_once('/')?;
+    let birth = birth.strip_prefix(['B', 'b'])?;
+    let survival = survival.strip_prefix(['S', 's'])?;
+    (birth.chars().chain(survival.chars()))
+        .all(|c| c.is_ascii_digit())
+        .then(|| format!("{survival}/{birth}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glider() -> Pattern {
+        let mut glider = Pattern::new(3, 3);
+        glider
+            .cells
+            .extend([(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        glider
+    }
+
+    #[test]
+    fn glider_round_trips() {
+        let mut glider = glider();
+        glider.name = Some("Glider".to_string());
+        glider.rule = Some("B3/S23".to_string());
+        glider.comments.push("The smallest spaceship".to_string());
+
+        let written = write(&glider);
+        assert_eq!(
+            written,
+            "#Life 1.05\n#N Glider\n#D The smallest spaceship\n#N\n#P -1 -1\n.*.\n..*\n***\n"
+        );
+        assert_eq!(parse(&written).unwrap(), glider);
+
+        glider.rule = Some("B36/S23".to_string());
+        let written = write(&glider);
+        assert!(written.contains("#R 23/36\n"));
+        assert_eq!(parse(&written).unwrap(), glider);
+    }
+
+    #[test]
+    fn empty_patterns_round_trip() {
+        let empty = Pattern::new(0, 0);
+        assert_eq!(write(&empty), "#Life 1.05\n#P 0 0\n");
+        assert_eq!(parse(&write(&empty)).unwrap(), empty);
+    }
+
+    #[test]
+    fn blocks_are_placed_at_their_positions() {
+        let pattern = parse("#Life 1.05\n#P -1 -1\n.*\n#P 1 1\n*.*\n").unwrap();
+        assert_eq!(
+            pattern.cells,
+            [(0, 0), (1, 2), (3, 2)].into_iter().collect()
+        );
+
+        let err = parse("#Life 1.05\n**\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: expected a `#P x y` line before the cells"
+        );
+
+        let err = parse("#Life 1.05\n#P 0 0\n*\n#P 5000 0\n*\n").unwrap_err();
+        assert!(matches!(err, PatternError::TooLarge { width: 5001, .. }));
+        let err = parse("#Life 1.05\n#P 9223372036854775807 0\n.*\n").unwrap_err();
+        assert!(matches!(err, PatternError::Parse { line: 3, .. }));
+    }
+
+    #[test]
+    fn rules_that_are_not_counts_are_left_out() {
+        let mut glider = glider();
+        glider.rule = Some("B2-a/S12".to_string());
+        assert!(!write(&glider).contains("#R"));
+        assert_eq!(parse(&write(&glider)).unwrap().rule, None);
+    }
+}
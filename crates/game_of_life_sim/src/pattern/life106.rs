@@ -0,0 +1,80 @@
+use super::{Pattern, PatternError};
+
+/// The first line of a Life 1.06 file
+pub const HEADER: &str = "#Life 1.06";
+
+/// Parse a Life 1.06 pattern, a list of the `x y` coordinates of the living cells
+pub fn parse(source: &str) -> Result<Pattern, PatternError> {
+    let mut cells = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let coordinates = line
+            .split_whitespace()
+            .map(|n| n.parse::<i64>())
+            .collect::<Result<Vec<_>, _>>();
+        match coordinates.as_deref() {
+            Ok(&[x, y]) => cells.push((x, y)),
+            _ => {
+                return Err(PatternError::Parse {
+                    line: number + 1,
+                    message: format!("expected the coordinates of a cell, found `{line}`"),
+                })
+            }
+        }
+    }
+
+    Pattern::from_cells(cells)
+}
+
+/// Write a pattern in Life 1.06 format, the format has no room for the name, rule or comments and
+/// the bounding box shrinks to the living cells
+pub fn write(pattern: &Pattern) -> String {
+    let mut output = format!("{HEADER}\n");
+    for (x, y) in &pattern.cells {
+        output.push_str(&format!("{x} {y}\n"));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glider_round_trips() {
+        let mut glider = Pattern::new(3, 3);
+        glider
+            .cells
+            .extend([(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+
+        let written = write(&glider);
+        assert_eq!(written, "#Life 1.06\n0 2\n1 0\n1 2\n2 1\n2 2\n");
+        assert_eq!(parse(&written).unwrap(), glider);
+    }
+
+    #[test]
+    fn empty_patterns_round_trip() {
+        let empty = Pattern::new(0, 0);
+        assert_eq!(write(&empty), "#Life 1.06\n");
+        assert_eq!(parse(&write(&empty)).unwrap(), empty);
+    }
+
+    #[test]
+    fn negative_coordinates_move_to_the_origin() {
+        let pattern = parse("#Life 1.06\n-1 -1\n1 0\n").unwrap();
+        assert_eq!(pattern.cells, [(0, 0), (2, 1)].into_iter().collect());
+
+        let err = parse("#Life 1.06\n1 2 3\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: expected the coordinates of a cell, found `1 2 3`"
+        );
+
+        let err = parse("#Life 1.06\n0 0\n4294967296 0\n").unwrap_err();
+        assert!(matches!(err, PatternError::TooLarge { .. }));
+    }
+}
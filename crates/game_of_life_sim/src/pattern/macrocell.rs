@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+
+use super::{
+    quadtree::{NodeId, QuadTree, EMPTY, MAX_LEVEL},
+    Pattern, PatternError,
+};
+
+/// The first line of a Macrocell file
+pub const HEADER: &str = "[M2]";
+
+/// The level of the 8 by 8 squares written as rows of cells
+const LEAF_LEVEL: u32 = 3;
+
+/// Parse a Macrocell pattern into its quadtree: `#R` rule and `#C` comment lines, then one node
+/// per line numbered from 1. A node is either 8 by 8 cells as rows of `.` and `*` ended by `$`, or
+/// `level nw ne sw se` with the numbers of its quadrants, 0 for an empty one. At level 1 the
+/// quadrants are the states of the cells of multi-state patterns. The last node is the root. The
+/// name, rule and comments are returned in an otherwise empty pattern.
+pub fn parse_tree(source: &str) -> Result<(QuadTree, Pattern), PatternError> {
+    let mut tree = QuadTree::new();
+    let mut metadata = Pattern::default();
+    // The node and level of each numbered line, the empty node is number 0
+    let mut nodes: Vec<(NodeId, u32)> = vec![(EMPTY, 0)];
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| PatternError::Parse {
+            line: number + 1,
+            message,
+        };
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with(HEADER) {
+            continue;
+        }
+        if let Some(directive) = line.strip_prefix('#') {
+            let mut chars = directive.chars();
+            let kind = chars.next();
+            let argument = chars.as_str().trim().to_string();
+            match kind {
+                Some('N') => metadata.name = Some(argument),
+                Some('C' | 'D') => metadata.comments.push(argument),
+                Some('R') => metadata.rule = Some(argument),
+                _ => {}
+            }
+            continue;
+        }
+
+        if line.starts_with(['.', '*', '$']) {
+            let cells = parse_leaf(line).map_err(error)?;
+            nodes.push((tree.build(LEAF_LEVEL, cells), LEAF_LEVEL));
+            continue;
+        }
+
+        let numbers = line
+            .split_whitespace()
+            .map(|n| n.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>();
+        let Ok(&[level, nw, ne, sw, se]) = numbers.as_deref() else {
+            return Err(error(format!(
+                "expected a node like `4 1 0 2 3`, found `{line}`"
+            )));
+        };
+        let level = level as u32;
+        if !(1..=MAX_LEVEL).contains(&level) {
+            return Err(error(format!(
+                "level {level} is not between 1 and {MAX_LEVEL}"
+            )));
+        }
+
+        let mut children = [nw, ne, sw, se];
+        if level == 1 {
+            // Only the living state of multi-state patterns is kept, the others are dying cells
+            children = children.map(|state| (state == 1) as NodeId);
+        } else {
+            for child in &mut children {
+                *child = match nodes.get(*child) {
+                    Some(&(id, _)) if id == EMPTY => EMPTY,
+                    Some(&(id, child_level)) if child_level == level - 1 => id,
+                    Some(_) => {
+                        return Err(error(format!(
+                            "node {child} is not of level {} to be part of a node of level {level}",
+                            level - 1
+                        )))
+                    }
+                    None => return Err(error(format!("node {child} is not defined yet"))),
+                };
+            }
+        }
+        nodes.push((tree.join(level, children), level));
+    }
+
+    if let Some(&(root, level)) = nodes.last() {
+        tree.set_root(root, level.max(1));
+    }
+    Ok((tree, metadata))
+}
+
+/// The living cells of an 8 by 8 square written as rows of `.` and `*` each ended by `$`
+fn parse_leaf(line: &str) -> Result<Vec<(u64, u64)>, String> {
+    let mut cells = Vec::new();
+    let (mut x, mut y) = (0, 0);
+    for c in line.chars() {
+        match c {
+            '.' => x += 1,
+            '*' => {
+                cells.push((x, y));
+                x += 1;
+            }
+            '$' => (x, y) = (0, y + 1),
+            c => return Err(format!("unexpected character `{c}`")),
+        }
+        if x > 8 || (y >= 8 && c != '$') {
+            return Err("a row of cells holds more than 8 by 8 cells".to_string());
+        }
+    }
+    Ok(cells)
+}
+
+/// Parse a Macrocell pattern and expand it into its cells, failing if it doesn't fit on the canvas
+pub fn parse(source: &str) -> Result<Pattern, PatternError> {
+    let (tree, metadata) = parse_tree(source)?;
+    Ok(Pattern {
+        name: metadata.name,
+        rule: metadata.rule,
+        comments: metadata.comments,
+        ..tree.to_pattern()?
+    })
+}
+
+/// Write a pattern in Macrocell format, the bounding box shrinks to the living cells when it is
+/// read back
+pub fn write(pattern: &Pattern) -> String {
+    let mut tree = QuadTree::from_pattern(pattern);
+    // Squares smaller than the 8 by 8 leaves are only written in multi-state patterns
+    if tree.level() < LEAF_LEVEL {
+        let mut root = tree.root();
+        for level in tree.level() + 1..=LEAF_LEVEL {
+            root = tree.join(level, [root, EMPTY, EMPTY, EMPTY]);
+        }
+        tree.set_root(root, LEAF_LEVEL);
+    }
+
+    let mut output = format!("{HEADER} (game_of_life_sim)\n");
+    if let Some(rule) = &pattern.rule {
+        output.push_str(&format!("#R {rule}\n"));
+    }
+    if let Some(name) = &pattern.name {
+        output.push_str(&format!("#N {name}\n"));
+    }
+    for comment in &pattern.comments {
+        output.push_str(&format!("#C {comment}\n"));
+    }
+    write_tree(&tree, &mut output);
+    output
+}
+
+/// Write the nodes of a tree, each after the quadrants it is made of and ending with the root
+pub fn write_tree(tree: &QuadTree, output: &mut String) {
+    let mut numbers = HashMap::new();
+    write_node(tree, tree.root(), &mut numbers, output);
+}
+
+/// Write a node unless it was written before, returning its number
+fn write_node(
+    tree: &QuadTree,
+    id: NodeId,
+    numbers: &mut HashMap<NodeId, usize>,
+    output: &mut String,
+) -> usize {
+    if id == EMPTY {
+        return 0;
+    }
+    if let Some(&number) = numbers.get(&id) {
+        return number;
+    }
+
+    let node = tree.node(id);
+    if node.level == LEAF_LEVEL {
+        let mut cells = tree.cells(id);
+        cells.sort_unstable_by_key(|&(x, y)| (y, x));
+        let mut rows = vec![String::new(); 8];
+        for (x, y) in cells {
+            let row = &mut rows[y as usize];
+            while row.len() < x as usize {
+                row.push('.');
+            }
+            row.push('*');
+        }
+        while rows.last().is_some_and(String::is_empty) {
+            rows.pop();
+        }
+        for row in rows {
+            output.push_str(&row);
+            output.push('$');
+        }
+    } else {
+        let children = node
+            .children
+            .map(|child| write_node(tree, child, numbers, output));
+        let [nw, ne, sw, se] = children;
+        output.push_str(&format!("{} {nw} {ne} {sw} {se}", node.level));
+    }
+    output.push('\n');
+
+    numbers.insert(id, numbers.len() + 1);
+    numbers.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_subtrees_are_expanded_everywhere() {
+        let pattern = parse("[M2]\n.*$\n4 1 1 1 1\n5 2 2 2 2\n").unwrap();
+        assert_eq!(pattern.cells.len(), 16);
+        assert!(pattern.is_alive(0, 0));
+        assert!(pattern.is_alive(8, 0));
+        assert!(pattern.is_alive(24, 24));
+        assert_eq!((pattern.width, pattern.height), (25, 25));
+    }
+
+    #[test]
+    fn level_one_nodes_keep_only_the_living_state() {
+        let source = "[M2]\n#R B2/S345/C4\n#N Dying\n1 1 2 0 1\n1 3 2 2 3\n2 1 2 0 1\n";
+        // Node 2 only holds dying cells and is empty
+        let pattern = parse(source).unwrap();
+        let cells = [(0, 0), (1, 1), (2, 2), (3, 3)];
+        assert_eq!(pattern.cells, cells.into_iter().collect());
+        assert_eq!(pattern.rule.as_deref(), Some("B2/S345/C4"));
+        assert_eq!(pattern.name.as_deref(), Some("Dying"));
+    }
+
+    #[test]
+    fn children_must_be_defined_and_one_level_down() {
+        let errors = [
+            ("[M2]\n.*$\n4 2 0 0 0\n", 3, "node 2 is not defined yet"),
+            ("[M2]\n.*$\n5 1 0 0 0\n", 3, "node 1 is not of level 4"),
+            (
+                "[M2]\n1 1 0 0 0\n3 1 0 0 0\n",
+                3,
+                "node 1 is not of level 2",
+            ),
+            ("[M2]\n0 0 0 0 0\n", 2, "level 0 is not between 1"),
+            ("[M2]\n4 1 0 0\n", 2, "expected a node"),
+            ("[M2]\n*********$\n", 2, "more than 8 by 8"),
+        ];
+        for (source, line, message) in errors {
+            match parse(source) {
+                Err(PatternError::Parse {
+                    line: at,
+                    message: found,
+                }) => {
+                    assert_eq!(at, line, "{source}");
+                    assert!(found.contains(message), "{found}");
+                }
+                other => panic!("{source}: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn large_trees_fail_before_expanding() {
+        let mut source = "[M2]\n".to_string();
+        source.push_str(&"********$".repeat(8));
+        source.push('\n');
+        for level in 4..=40 {
+            let node = level - 3;
+            source.push_str(&format!("{level} {node} {node} {node} {node}\n"));
+        }
+        assert!(matches!(
+            parse(&source),
+            Err(PatternError::TooLarge { width, height, .. })
+                if (width, height) == (1 << 40, 1 << 40)
+        ));
+    }
+
+    #[test]
+    fn patterns_larger_than_a_leaf_round_trip() {
+        let mut pattern = Pattern::new(0, 0);
+        pattern.name = Some("Blocks".to_string());
+        pattern.rule = Some("B3/S23".to_string());
+        for (x, y) in [(0, 0), (16, 0), (0, 16), (16, 16)] {
+            pattern.insert(x, y);
+            pattern.insert(x + 1, y);
+            pattern.insert(x, y + 1);
+            pattern.insert(x + 1, y + 1);
+        }
+        pattern.insert(30, 3);
+
+        let written = write(&pattern);
+        // The block repeats, its leaf is written once
+        assert_eq!(written.matches("**$**$").count(), 1);
+        assert_eq!(parse(&written).unwrap(), pattern);
+    }
+}
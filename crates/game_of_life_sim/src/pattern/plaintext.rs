@@ -0,0 +1,90 @@
+use super::{Pattern, PatternError};
+
+/// Parse a Plaintext `.cells` pattern: `!` lines are comments, `!Name:` names the pattern, and
+/// every other line is a row where `O` is a living cell and `.` a dead one
+pub fn parse(source: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let mut y = 0;
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim_end();
+        if let Some(comment) = line.strip_prefix('!') {
+            match comment.strip_prefix("Name:") {
+                Some(name) => pattern.name = Some(name.trim().to_string()),
+                None => pattern.comments.push(comment.trim().to_string()),
+            }
+            continue;
+        }
+
+        for (x, c) in line.chars().enumerate() {
+            match c {
+                'O' | '*' => pattern.insert(x as u32, y),
+                '.' => {}
+                c => {
+                    return Err(PatternError::Parse {
+                        line: number + 1,
+                        message: format!("unexpected character `{c}`"),
+                    })
+                }
+            }
+        }
+        pattern.width = pattern.width.max(line.chars().count() as u32);
+        y += 1;
+    }
+    pattern.height = pattern.height.max(y);
+
+    Ok(pattern)
+}
+
+/// Write a pattern in Plaintext format, rows are written in full to keep the bounding box
+pub fn write(pattern: &Pattern) -> String {
+    let mut output = String::new();
+    if let Some(name) = &pattern.name {
+        output.push_str(&format!("!Name: {name}\n"));
+    }
+    for comment in &pattern.comments {
+        output.push_str(&format!("!{comment}\n"));
+    }
+    for y in 0..pattern.height {
+        let row = (0..pattern.width)
+            .map(|x| if pattern.is_alive(x, y) { 'O' } else { '.' })
+            .collect::<String>();
+        output.push_str(&row);
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glider_round_trips() {
+        let mut glider = Pattern::new(3, 3);
+        glider
+            .cells
+            .extend([(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        glider.name = Some("Glider".to_string());
+        glider.comments.push("The smallest spaceship".to_string());
+
+        let written = write(&glider);
+        assert_eq!(
+            written,
+            "!Name: Glider\n!The smallest spaceship\n.O.\n..O\nOOO\n"
+        );
+        assert_eq!(parse(&written).unwrap(), glider);
+    }
+
+    #[test]
+    fn empty_patterns_round_trip() {
+        for empty in [Pattern::new(0, 0), Pattern::new(3, 2)] {
+            assert_eq!(parse(&write(&empty)).unwrap(), empty);
+        }
+    }
+
+    #[test]
+    fn unexpected_characters_are_errors() {
+        let err = parse("!Name: Block\nOO\nOx\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 3: unexpected character `x`");
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use super::{Pattern, PatternError};
+use crate::SIM_SIZE;
+
+/// The index of a node in a `QuadTree`, equal squares share a node
+pub type NodeId = usize;
+
+/// The node of every square without living cells, at any level
+pub const EMPTY: NodeId = 0;
+
+/// The deepest tree, the coordinates of its cells still fit in an `i64`
+pub const MAX_LEVEL: u32 = 62;
+
+/// A square of `2^level` cells on each side split into its four quadrants
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Node {
+    pub level: u32,
+    /// The nodes of the north west, north east, south west and south east quadrants. At level 1
+    /// these are the cells themselves, 1 for a living cell and 0 for a dead one.
+    pub children: [NodeId; 4],
+}
+
+/// A pattern stored as a quadtree where equal squares are stored once, so patterns far larger than
+/// the canvas take little memory as long as they repeat
+#[derive(Debug, Clone)]
+pub struct QuadTree {
+    nodes: Vec<Node>,
+    index: HashMap<Node, NodeId>,
+    root: NodeId,
+    level: u32,
+}
+
+impl Default for QuadTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QuadTree {
+    /// An empty tree
+    pub fn new() -> Self {
+        Self {
+            // A stand-in for `EMPTY` so the indices of the nodes start at 1
+            nodes: vec![Node {
+                level: 0,
+                children: [EMPTY; 4],
+            }],
+            index: HashMap::new(),
+            root: EMPTY,
+            level: 1,
+        }
+    }
+
+    /// The tree of the living cells of a pattern, with its bounding box in the top left corner
+    pub fn from_pattern(pattern: &Pattern) -> Self {
+        let size = pattern.width.max(pattern.height).max(2);
+        let level = u32::BITS - (size - 1).leading_zeros();
+        let mut tree = Self::new();
+        let cells = pattern
+            .cells
+            .iter()
+            .map(|&(x, y)| (x as u64, y as u64))
+            .collect();
+        let root = tree.build(level, cells);
+        tree.set_root(root, level);
+        tree
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// The root covers `2^level` cells on each side
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn node(&self, id: NodeId) -> Node {
+        self.nodes[id]
+    }
+
+    pub fn set_root(&mut self, root: NodeId, level: u32) {
+        self.root = root;
+        self.level = level;
+    }
+
+    /// The node of a square of `level` from its quadrants, creating it if no equal one exists
+    pub fn join(&mut self, level: u32, children: [NodeId; 4]) -> NodeId {
+        if children == [EMPTY; 4] {
+            return EMPTY;
+        }
+        let node = Node { level, children };
+        if let Some(&id) = self.index.get(&node) {
+            return id;
+        }
+        self.nodes.push(node);
+        self.index.insert(node, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// The node of a square of `level` with `cells` alive, relative to its top left corner
+    pub fn build(&mut self, level: u32, cells: Vec<(u64, u64)>) -> NodeId {
+        if cells.is_empty() {
+            return EMPTY;
+        }
+        let half = 1 << (level - 1);
+        if level == 1 {
+            let mut children = [0; 4];
+            for (x, y) in cells {
+                children[(y * 2 + x) as usize] = 1;
+            }
+            return self.join(level, children);
+        }
+
+        let mut quadrants: [Vec<(u64, u64)>; 4] = Default::default();
+        for (x, y) in cells {
+            let quadrant = (y >= half) as usize * 2 + (x >= half) as usize;
+            quadrants[quadrant].push((x % half, y % half));
+        }
+        let children = quadrants.map(|cells| self.build(level - 1, cells));
+        self.join(level, children)
+    }
+
+    /// The living cells of a node relative to its top left corner, in no particular order
+    pub fn cells(&self, id: NodeId) -> Vec<(u64, u64)> {
+        let mut cells = Vec::new();
+        self.collect_cells(id, (0, 0), &mut cells);
+        cells
+    }
+
+    fn collect_cells(&self, id: NodeId, (x, y): (u64, u64), cells: &mut Vec<(u64, u64)>) {
+        if id == EMPTY {
+            return;
+        }
+        let node = self.nodes[id];
+        let half = 1 << (node.level - 1);
+        for (quadrant, child) in node.children.into_iter().enumerate() {
+            let position = (
+                x + (quadrant as u64 % 2) * half,
+                y + (quadrant as u64 / 2) * half,
+            );
+            match node.level {
+                1 if child == 1 => cells.push(position),
+                1 => {}
+                _ => self.collect_cells(child, position, cells),
+            }
+        }
+    }
+
+    /// The smallest rectangle holding every living cell as (x, y, width, height), found without
+    /// visiting every cell
+    pub fn bounding_box(&self) -> Option<(u64, u64, u64, u64)> {
+        let mut known = HashMap::new();
+        let (min_x, min_y, max_x, max_y) = self.node_bounds(self.root, &mut known)?;
+        Some((min_x, min_y, max_x - min_x + 1, max_y - min_y + 1))
+    }
+
+    /// The first and last living column and row of a node, remembered for the nodes that repeat
+    fn node_bounds(
+        &self,
+        id: NodeId,
+        known: &mut HashMap<NodeId, Option<(u64, u64, u64, u64)>>,
+    ) -> Option<(u64, u64, u64, u64)> {
+        if id == EMPTY {
+            return None;
+        }
+        if let Some(&bounds) = known.get(&id) {
+            return bounds;
+        }
+
+        let node = self.nodes[id];
+        let half = 1 << (node.level - 1);
+        let mut bounds = None::<(u64, u64, u64, u64)>;
+        for (quadrant, child) in node.children.into_iter().enumerate() {
+            let (x, y) = ((quadrant as u64 % 2) * half, (quadrant as u64 / 2) * half);
+            let child_bounds = match node.level {
+                1 if child == 1 => Some((0, 0, 0, 0)),
+                1 => None,
+                _ => self.node_bounds(child, known),
+            };
+            if let Some((min_x, min_y, max_x, max_y)) = child_bounds {
+                let (min_x, min_y, max_x, max_y) = (x + min_x, y + min_y, x + max_x, y + max_y);
+                bounds = Some(match bounds {
+                    Some(b) => (
+                        b.0.min(min_x),
+                        b.1.min(min_y),
+                        b.2.max(max_x),
+                        b.3.max(max_y),
+                    ),
+                    None => (min_x, min_y, max_x, max_y),
+                });
+            }
+        }
+        known.insert(id, bounds);
+        bounds
+    }
+
+    /// Expand the tree into the cells of a pattern, trimmed to the living cells. Fails before
+    /// expanding anything if the pattern doesn't fit on the canvas.
+    pub fn to_pattern(&self) -> Result<Pattern, PatternError> {
+        let Some((_, _, width, height)) = self.bounding_box() else {
+            return Ok(Pattern::default());
+        };
+        if width > SIM_SIZE as u64 || height > SIM_SIZE as u64 {
+            return Err(PatternError::TooLarge {
+                width,
+                height,
+                offset: (0, 0),
+                canvas: (SIM_SIZE, SIM_SIZE),
+            });
+        }
+
+        let cells = self.cells(self.root);
+        Pattern::from_cells(cells.into_iter().map(|(x, y)| (x as i64, y as i64)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_round_trip() {
+        let mut glider = Pattern::new(3, 3);
+        glider
+            .cells
+            .extend([(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        let tree = QuadTree::from_pattern(&glider);
+        assert_eq!(tree.level(), 2);
+        assert_eq!(tree.bounding_box(), Some((0, 0, 3, 3)));
+        assert_eq!(tree.to_pattern().unwrap(), glider);
+
+        assert_eq!(QuadTree::new().to_pattern().unwrap(), Pattern::default());
+    }
+
+    #[test]
+    fn equal_squares_share_a_node() {
+        let mut tree = QuadTree::new();
+        let block = tree.build(2, vec![(0, 0), (1, 0), (0, 1), (1, 1)]);
+        let twice = tree.join(3, [block, EMPTY, EMPTY, block]);
+        assert_eq!(
+            tree.build(3, vec![(0, 0), (1, 0), (0, 1), (1, 1)]),
+            tree.join(3, [block, EMPTY, EMPTY, EMPTY])
+        );
+        assert_eq!(tree.join(3, [block, EMPTY, EMPTY, block]), twice);
+        assert_eq!(tree.cells(twice).len(), 8);
+        assert_eq!(tree.join(4, [EMPTY; 4]), EMPTY);
+    }
+
+    #[test]
+    fn trees_larger_than_the_canvas_fail_before_expanding() {
+        let mut tree = QuadTree::new();
+        let root = tree.build(12, vec![(0, 0), (4095, 2)]);
+        tree.set_root(root, 12);
+        assert_eq!(tree.bounding_box(), Some((0, 0, 4096, 3)));
+        assert!(matches!(
+            tree.to_pattern(),
+            Err(PatternError::TooLarge {
+                width: 4096,
+                height: 3,
+                ..
+            })
+        ));
+
+        // Every cell of a 2^50 square is alive, expanding it would never finish
+        let mut tree = QuadTree::new();
+        let mut square = tree.join(1, [1; 4]);
+        for level in 2..=50 {
+            square = tree.join(level, [square; 4]);
+        }
+        tree.set_root(square, 50);
+        assert_eq!(tree.bounding_box(), Some((0, 0, 1 << 50, 1 << 50)));
+        assert!(matches!(
+            tree.to_pattern(),
+            Err(PatternError::TooLarge { width, .. }) if width == 1 << 50
+        ));
+    }
+}
@@ -0,0 +1,328 @@
+use super::{Pattern, PatternError};
+use crate::SIM_SIZE;
+
+/// Lines of the encoded cells are wrapped before this many characters, as the format asks
+const MAX_LINE_LENGTH: usize = 70;
+
+/// Parse a Run Length Encoded pattern: `#` lines, the `x = .., y = .., rule = ..` header and runs
+/// of cells like `3o2b$`. `b` and `.` are dead cells and `o` and `A` living ones. The other states
+/// of multi-state patterns are dying cells, which are not kept. Fails on the first run of living
+/// cells that reaches past the canvas, before any of its cells are kept.
+pub fn parse(source: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let mut header = false;
+    let (mut x, mut y) = (0u32, 0u32);
+    let mut count = None::<u32>;
+    // The `p` to `y` prefix of the multi-state letters above `X`
+    let mut prefixed = false;
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| PatternError::Parse {
+            line: number + 1,
+            message,
+        };
+        let line = line.trim();
+
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut chars = comment.chars();
+            let kind = chars.next();
+            let text = chars.as_str().trim().to_string();
+            match kind {
+                Some('N') => pattern.name = Some(text),
+                Some('C' | 'c') => pattern.comments.push(text),
+                Some('r') => pattern.rule = Some(text),
+                _ => {}
+            }
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if !header {
+            header = true;
+            parse_header(line, &mut pattern).map_err(error)?;
+            continue;
+        }
+
+        for c in line.chars() {
+            match c {
+                '0'..='9' => {
+                    let digit = c.to_digit(10).unwrap();
+                    let run = count
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add(digit));
+                    count = Some(run.ok_or_else(|| error("run count is too large".to_string()))?);
+                    continue;
+                }
+                'p'..='y' if !prefixed => {
+                    prefixed = true;
+                    continue;
+                }
+                c if c.is_whitespace() => continue,
+                _ => {}
+            }
+
+            let run = count.take().unwrap_or(1);
+            let was_prefixed = std::mem::take(&mut prefixed);
+            let skip = |from: u32| {
+                from.checked_add(run)
+                    .ok_or_else(|| error("the pattern is too large".to_string()))
+            };
+            match c {
+                '!' => return Ok(pattern),
+                '$' => {
+                    x = 0;
+                    y = skip(y)?;
+                }
+                'b' | '.' => x = skip(x)?,
+                // Dying states of multi-state patterns, with a prefix the states above `X`
+                'A'..='X' if was_prefixed => x = skip(x)?,
+                'B'..='X' => x = skip(x)?,
+                c if c.is_ascii_alphabetic() => {
+                    let end = x as u64 + run as u64;
+                    if end > SIM_SIZE as u64 || y >= SIM_SIZE {
+                        return Err(PatternError::TooLarge {
+                            width: end.max(pattern.width as u64),
+                            height: (y as u64 + 1).max(pattern.height as u64),
+                            offset: (0, 0),
+                            canvas: (SIM_SIZE, SIM_SIZE),
+                        });
+                    }
+                    for _ in 0..run {
+                        pattern.insert(x, y);
+                        x += 1;
+                    }
+                }
+                c => return Err(error(format!("unexpected character `{c}`"))),
+            }
+        }
+    }
+
+    if !header {
+        return Err(PatternError::Parse {
+            line: 1,
+            message: "expected a header like `x = 3, y = 3`".to_string(),
+        });
+    }
+    Ok(pattern)
+}
+
+/// Parse `x = m, y = n, rule = B3/S23` into the bounding box and rule of `pattern`
+fn parse_header(line: &str, pattern: &mut Pattern) -> Result<(), String> {
+    let (mut width, mut height) = (None, None);
+    for entry in line.split(',') {
+        let Some((key, value)) = entry.split_once('=') else {
+            return Err(format!(
+                "expected `key = value` in the header, found `{entry}`"
+            ));
+        };
+        let value = value.trim();
+        let size = || {
+            value
+                .parse::<u32>()
+                .map_err(|_| format!("`{value}` is not a valid size"))
+        };
+        match key.trim() {
+            "x" => width = Some(size()?),
+            "y" => height = Some(size()?),
+            "rule" => pattern.rule = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    match (width, height) {
+        (Some(width), Some(height)) => {
+            pattern.width = pattern.width.max(width);
+            pattern.height = pattern.height.max(height);
+            Ok(())
+        }
+        _ => Err("the header needs both `x` and `y`".to_string()),
+    }
+}
+
+/// Write a pattern in Run Length Encoded format, dead cells at the end of a row are left out and
+/// empty rows are merged into the count of the `$` before them
+pub fn write(pattern: &Pattern) -> String {
+    let mut output = String::new();
+    if let Some(name) = &pattern.name {
+        output.push_str(&format!("#N {name}\n"));
+    }
+    for comment in &pattern.comments {
+        output.push_str(&format!("#C {comment}\n"));
+    }
+    output.push_str(&format!("x = {}, y = {}", pattern.width, pattern.height));
+    match &pattern.rule {
+        Some(rule) => output.push_str(&format!(", rule = {rule}\n")),
+        None => output.push('\n'),
+    }
+
+    // Runs of (count, tag) for the whole pattern
+    let mut runs: Vec<(u32, char)> = Vec::new();
+    let mut push = |count: u32, tag: char| match runs.last_mut() {
+        Some((last, last_tag)) if *last_tag == tag => *last += count,
+        _ => runs.push((count, tag)),
+    };
+    let mut x = 0;
+    let mut y = 0;
+    let mut cells = pattern
+        .cells
+        .iter()
+        .map(|&(x, y)| (y, x))
+        .collect::<Vec<_>>();
+    cells.sort_unstable();
+    for (cell_y, cell_x) in cells {
+        if cell_y > y {
+            push(cell_y - y, '$');
+            (x, y) = (0, cell_y);
+        }
+        if cell_x > x {
+            push(cell_x - x, 'b');
+        }
+        push(1, 'o');
+        x = cell_x + 1;
+    }
+    push(1, '!');
+
+    let mut line = String::new();
+    for (count, tag) in runs {
+        let run = match count {
+            1 => tag.to_string(),
+            count => format!("{count}{tag}"),
+        };
+        if line.len() + run.len() > MAX_LINE_LENGTH {
+            output.push_str(&line);
+            output.push('\n');
+            line.clear();
+        }
+        line.push_str(&run);
+    }
+    output.push_str(&line);
+    output.push('\n');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glider() -> Pattern {
+        let mut glider = Pattern::new(3, 3);
+        glider
+            .cells
+            .extend([(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        glider
+    }
+
+    #[test]
+    fn header_sets_the_size_and_rule() {
+        let pattern =
+            parse("#N Glider\n#C A comment\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!").unwrap();
+        assert_eq!(
+            pattern,
+            Pattern {
+                name: Some("Glider".to_string()),
+                rule: Some("B3/S23".to_string()),
+                comments: vec!["A comment".to_string()],
+                ..glider()
+            }
+        );
+
+        // The header can be larger than the cells, the bounding box keeps its size
+        let pattern = parse("x = 10, y = 4\no!").unwrap();
+        assert_eq!((pattern.width, pattern.height, pattern.rule), (10, 4, None));
+    }
+
+    #[test]
+    fn header_errors_point_at_the_line() {
+        let err = parse("#C no size\nx = 3\n3o!").unwrap_err();
+        assert!(
+            matches!(&err, PatternError::Parse { line: 2, message } if message.contains("`y`")),
+            "{err}"
+        );
+        let err = parse("x = three, y = 1\n3o!").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: `three` is not a valid size");
+        assert!(matches!(
+            parse("#C only comments"),
+            Err(PatternError::Parse { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn runs_past_the_canvas_fail_before_inserting() {
+        for source in [
+            "x = 1, y = 1\n4294967295b2o!",
+            "x = 1, y = 1\n4294967295$o$o!",
+        ] {
+            assert!(matches!(parse(source), Err(PatternError::TooLarge { .. })));
+        }
+        for source in [
+            "x = 1, y = 1\n4294967295b2bo!",
+            "x = 1, y = 1\n4294967295$$o!",
+        ] {
+            assert!(matches!(
+                parse(source),
+                Err(PatternError::Parse { line: 2, .. })
+            ));
+        }
+
+        let too_wide = parse("x = 1, y = 1\n4000000000o!");
+        assert!(matches!(
+            too_wide,
+            Err(PatternError::TooLarge {
+                width: 4_000_000_000,
+                height: 1,
+                ..
+            })
+        ));
+        let too_tall = parse(&format!("x = 1, y = 1\n{SIM_SIZE}$o!"));
+        assert!(matches!(too_tall, Err(PatternError::TooLarge { .. })));
+
+        let fits = parse(&format!("x = 1, y = 1\n{}bo!", SIM_SIZE - 1)).unwrap();
+        assert_eq!(fits.width, SIM_SIZE);
+    }
+
+    #[test]
+    fn multi_digit_runs() {
+        let pattern = parse("x = 14, y = 12\n12b2o11$\n3o!").unwrap();
+        let cells = [(12, 0), (13, 0), (0, 11), (1, 11), (2, 11)];
+        assert_eq!(pattern.cells, cells.into_iter().collect());
+        assert_eq!((pattern.width, pattern.height), (14, 12));
+
+        let err = parse("x = 1, y = 1\n99999999999o!").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: run count is too large");
+    }
+
+    #[test]
+    fn multi_state_prefixes() {
+        // `A` is alive on its own and a dying state after a `p` to `y` prefix, `B` to `X` are
+        // always dying states
+        let pattern = parse("x = 9, y = 2, rule = 23/3/40\n.AByO2pAA$qX2A!").unwrap();
+        let cells = [(1, 0), (6, 0), (1, 1), (2, 1)];
+        assert_eq!(pattern.cells, cells.into_iter().collect());
+        assert_eq!(pattern.rule.as_deref(), Some("23/3/40"));
+    }
+
+    #[test]
+    fn patterns_round_trip() {
+        let mut glider = glider();
+        glider.name = Some("Glider".to_string());
+        glider.rule = Some("B3/S23".to_string());
+        glider.comments.push("The smallest spaceship".to_string());
+        assert_eq!(parse(&write(&glider)).unwrap(), glider);
+
+        // Long runs and more runs than fit on a line
+        let mut wide = Pattern::new(200, 120);
+        for x in (0..200).step_by(3) {
+            wide.insert(x, x % 7 + 100);
+        }
+        wide.insert(199, 119);
+        let written = write(&wide);
+        assert!(written.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert_eq!(parse(&written).unwrap(), wide);
+
+        let empty = Pattern::new(0, 0);
+        assert_eq!(write(&empty), "x = 0, y = 0\n!\n");
+        assert_eq!(parse(&write(&empty)).unwrap(), empty);
+    }
+}
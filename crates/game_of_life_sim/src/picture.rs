@@ -0,0 +1,239 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use glass::wgpu;
+use image::{DynamicImage, RgbaImage};
+
+use crate::{cpu::Grid, pattern::Pattern};
+
+/// Pixels at least this bright are living cells when an image is read as a pattern
+pub const DEFAULT_THRESHOLD: u8 = 128;
+
+/// The largest number of states an indexed PNG has colors for
+const MAX_STATES: u32 = 256;
+
+/// Write the cells of a grid as an indexed PNG with one pixel per cell. Dead cells are black,
+/// living cells white and the dying states of Generations rules shades of gray too dark to be
+/// read back as living. Two states take a single bit per pixel.
+pub fn write_grid_png(grid: &Grid, states: u32, writer: impl Write) -> Result<(), PictureError> {
+    let states = states.clamp(2, MAX_STATES);
+    let depth = match states {
+        2 => png::BitDepth::One,
+        3..=4 => png::BitDepth::Two,
+        5..=16 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    };
+    let bits = depth as u32;
+
+    let mut palette = vec![0, 0, 0, 255, 255, 255];
+    for state in 2..states {
+        let shade = (100 * (states - state) / (states - 2)) as u8;
+        palette.extend([shade; 3]);
+    }
+
+    // Rows are packed from the highest bits of each byte and start at a new byte
+    let row_size = (grid.width() * bits).div_ceil(8) as usize;
+    let mut data = vec![0u8; row_size * grid.height() as usize];
+    for y in 0..grid.height() {
+        let row = &mut data[y as usize * row_size..][..row_size];
+        for x in 0..grid.width() {
+            let index = grid.get(x, y).min(states - 1) as u8;
+            let bit = x * bits;
+            row[(bit / 8) as usize] |= index << (8 - bits - bit % 8);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(writer, grid.width(), grid.height());
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(depth);
+    encoder.set_palette(palette);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    Ok(writer.finish()?)
+}
+
+/// Save the cells of a grid to a PNG file, see `write_grid_png`
+pub fn save_grid_png(grid: &Grid, states: u32, path: impl AsRef<Path>) -> Result<(), PictureError> {
+    let file = BufWriter::new(File::create(path)?);
+    write_grid_png(grid, states, file)
+}
+
+/// A pattern with a cell for every pixel of an image, alive where the pixel is opaque and at least
+/// as bright as `threshold`. Invert the image first to read dark cells on a light background.
+pub fn pattern_from_image(image: &DynamicImage, threshold: u8) -> Pattern {
+    let image = image.to_luma_alpha8();
+    let mut pattern = Pattern::new(image.width(), image.height());
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let [luma, alpha] = pixel.0;
+        if luma >= threshold && alpha >= 128 {
+            pattern.cells.insert((x, y));
+        }
+    }
+    pattern
+}
+
+/// Read an image file in any format the `image` crate knows as a pattern, see
+/// `pattern_from_image`
+pub fn read_image_pattern(path: impl AsRef<Path>, threshold: u8) -> Result<Pattern, PictureError> {
+    Ok(pattern_from_image(&image::open(path)?, threshold))
+}
+
+/// An image from the bytes of a texture of `format`, for the 8 bit RGBA and BGRA formats that
+/// windows render to
+pub fn rgba_image(
+    mut bytes: Vec<u8>,
+    (width, height): (u32, u32),
+    format: wgpu::TextureFormat,
+) -> Result<RgbaImage, PictureError> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {}
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+            for texel in bytes.chunks_exact_mut(4) {
+                texel.swap(0, 2);
+            }
+        }
+        format => return Err(PictureError::UnsupportedFormat(format)),
+    }
+    Ok(RgbaImage::from_raw(width, height, bytes).expect("the texture is `width` by `height`"))
+}
+
+/// An error produced while writing or reading an image of the cells or capturing the frame
+#[derive(Debug)]
+pub enum PictureError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Png(png::EncodingError),
+    /// The frame couldn't be read back from the GPU
+    Readback(wgpu::BufferAsyncError),
+    /// A frame rendered in a format that can't be turned into an image
+    UnsupportedFormat(wgpu::TextureFormat),
+}
+
+impl fmt::Display for PictureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PictureError::Io(err) => write!(f, "{err}"),
+            PictureError::Image(err) => write!(f, "{err}"),
+            PictureError::Png(err) => write!(f, "{err}"),
+            PictureError::Readback(err) => write!(f, "failed to read the frame: {err}"),
+            PictureError::UnsupportedFormat(format) => {
+                write!(f, "frames rendered as {format:?} can't be saved as images")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PictureError {}
+
+impl From<std::io::Error> for PictureError {
+    fn from(err: std::io::Error) -> Self {
+        PictureError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for PictureError {
+    fn from(err: image::ImageError) -> Self {
+        PictureError::Image(err)
+    }
+}
+
+impl From<png::EncodingError> for PictureError {
+    fn from(err: png::EncodingError) -> Self {
+        PictureError::Png(err)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for PictureError {
+    fn from(err: wgpu::BufferAsyncError) -> Self {
+        PictureError::Readback(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A grid that holds every state, with a width that doesn't fill the last byte of a row
+    fn grid(states: u32) -> Grid {
+        let mut grid = Grid::new(13, 7);
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                grid.set(x, y, (x + y * 13) % states);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn grids_round_trip_at_every_bit_depth() {
+        let depths = [
+            (2, png::BitDepth::One),
+            (3, png::BitDepth::Two),
+            (4, png::BitDepth::Two),
+            (5, png::BitDepth::Four),
+            (16, png::BitDepth::Four),
+            (17, png::BitDepth::Eight),
+            (256, png::BitDepth::Eight),
+        ];
+        for (states, depth) in depths {
+            let grid = grid(states);
+            let mut bytes = Vec::new();
+            write_grid_png(&grid, states, &mut bytes).unwrap();
+
+            let decoder = png::Decoder::new(bytes.as_slice());
+            let info = decoder.read_info().unwrap().info().clone();
+            assert_eq!(info.bit_depth, depth, "{states} states");
+            assert_eq!(info.color_type, png::ColorType::Indexed);
+
+            // Only the living state reads back, the dying shades stay below the threshold
+            let image = image::load_from_memory(&bytes).unwrap();
+            let pattern = pattern_from_image(&image, DEFAULT_THRESHOLD);
+            assert_eq!(pattern, Pattern::from_grid(&grid), "{states} states");
+
+            let luma = image.to_luma8();
+            for y in 0..grid.height() {
+                for x in 0..grid.width() {
+                    let shade = luma.get_pixel(x, y).0[0];
+                    match grid.get(x, y) {
+                        0 => assert_eq!(shade, 0),
+                        1 => assert_eq!(shade, 255),
+                        _ => assert!(shade < DEFAULT_THRESHOLD, "{shade}"),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn states_past_the_palette_are_clamped() {
+        let mut grid = Grid::new(2, 1);
+        grid.set(0, 0, 1);
+        grid.set(1, 0, 1000);
+        let mut bytes = Vec::new();
+        write_grid_png(&grid, 2048, &mut bytes).unwrap();
+        let image = image::load_from_memory(&bytes).unwrap().to_luma8();
+        assert_eq!(image.get_pixel(0, 0).0, [255]);
+        assert!(image.get_pixel(1, 0).0[0] < DEFAULT_THRESHOLD);
+    }
+
+    #[test]
+    fn frames_are_read_as_rgba() {
+        let bytes = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let rgba = rgba_image(bytes.clone(), (2, 1), wgpu::TextureFormat::Rgba8UnormSrgb).unwrap();
+        assert_eq!(rgba.into_raw(), bytes);
+
+        let bgra = rgba_image(bytes.clone(), (2, 1), wgpu::TextureFormat::Bgra8Unorm).unwrap();
+        assert_eq!(bgra.get_pixel(0, 0).0, [3, 2, 1, 4]);
+        assert_eq!(bgra.get_pixel(1, 0).0, [7, 6, 5, 8]);
+
+        let err = rgba_image(bytes, (1, 1), wgpu::TextureFormat::Rgba16Float).unwrap_err();
+        assert!(matches!(
+            err,
+            PictureError::UnsupportedFormat(wgpu::TextureFormat::Rgba16Float)
+        ));
+    }
+}
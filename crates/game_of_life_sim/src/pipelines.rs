@@ -3,7 +3,16 @@ use glass::{
     GlassContext,
 };
 
-use crate::{shaders::ShaderImportProcessor, GameOfLifePushConstants};
+use crate::{
+    dsl,
+    shaders::{ShaderError, ShaderImportProcessor},
+    Boundary, GameOfLifePushConstants, WORK_GROUP_SIZE,
+};
+
+/// The shader in `assets` the draw pipeline is built from
+pub const DRAW_SHADER: &str = "draw.wgsl";
+/// The shader in `assets` the init and update pipelines are built from
+pub const GAME_OF_LIFE_SHADER: &str = "game_of_life.wgsl";
 
 pub struct Pipelines {
     pub draw_pipeline: wgpu::ComputePipeline,
@@ -12,7 +21,9 @@ pub struct Pipelines {
 }
 
 impl Pipelines {
-    fn create_draw_pipeline(context: &mut GlassContext) -> wgpu::ComputePipeline {
+    fn create_draw_pipeline(
+        context: &mut GlassContext,
+    ) -> Result<wgpu::ComputePipeline, ShaderError> {
         let dr_layout =
             context
                 .device()
@@ -30,9 +41,11 @@ impl Pipelines {
                     label: Some("draw_bind_group_layout"),
                 });
 
-        let brush_shader = ShaderImportProcessor::default()
-            .load_shader(context.device(), "draw.wgsl", Some("draw_shader"))
-            .unwrap();
+        let brush_shader = Self::shader_processor()?.load_shader(
+            context.device(),
+            DRAW_SHADER,
+            Some("draw_shader"),
+        )?;
 
         let draw_layout =
             context
@@ -46,14 +59,14 @@ impl Pipelines {
                     }],
                 });
 
-        context
+        Ok(context
             .device()
             .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
                 label: Some("Draw Pipeline"),
                 layout: Some(&draw_layout),
                 module: &brush_shader,
                 entry_point: "main",
-            })
+            }))
     }
 
     fn create_init_pipeline(
@@ -104,54 +117,182 @@ impl Pipelines {
             })
     }
 
-    pub fn load(context: &mut GlassContext) -> Self {
-        let bg_layout =
-            context
-                .device()
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            count: None,
-                            visibility: wgpu::ShaderStages::COMPUTE,
-                            ty: wgpu::BindingType::StorageTexture {
-                                access: StorageTextureAccess::ReadWrite,
-                                view_dimension: wgpu::TextureViewDimension::D2,
-                                format: wgpu::TextureFormat::Rgba16Float,
-                            },
+    fn create_bind_group_layout(context: &mut GlassContext) -> wgpu::BindGroupLayout {
+        context
+            .device()
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        count: None,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: StorageTextureAccess::ReadWrite,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            format: wgpu::TextureFormat::Rgba16Float,
+                        },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        count: None,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: StorageTextureAccess::ReadWrite,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            format: wgpu::TextureFormat::Rgba16Float,
                         },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 1,
-                            count: None,
-                            visibility: wgpu::ShaderStages::COMPUTE,
-                            ty: wgpu::BindingType::StorageTexture {
-                                access: StorageTextureAccess::ReadWrite,
-                                view_dimension: wgpu::TextureViewDimension::D2,
-                                format: wgpu::TextureFormat::Rgba16Float,
-                            },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        count: None,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
-                    ],
-                    label: Some("gol_bind_group_layout"),
-                });
+                    },
+                ],
+                label: Some("gol_bind_group_layout"),
+            })
+    }
 
-        let game_of_life_shader = ShaderImportProcessor::default()
-            .load_shader_with_dsl(
-                context.device(),
-                "game_of_life.wgsl",
-                &crate::dsl::rulesets::conways_game_of_life(),
-                Some("game_of_life_shader"),
-            )
-            .unwrap();
-
-        let draw_pipeline = Self::create_draw_pipeline(context);
-        let init_pipeline = Self::create_init_pipeline(context, &bg_layout, &game_of_life_shader);
-        let game_of_life_pipeline =
-            Self::create_compute_pipeline(context, &bg_layout, &game_of_life_shader);
-
-        Self {
-            init_pipeline,
-            draw_pipeline,
-            game_of_life_pipeline,
+    /// The processor every shader is loaded with, it defines the values the shaders share with
+    /// the dispatches
+    fn shader_processor() -> Result<ShaderImportProcessor, ShaderError> {
+        Ok(ShaderImportProcessor::with_asset_modules()?
+            .with_define("WORKGROUP_SIZE", WORK_GROUP_SIZE))
+    }
+
+    /// The processor the update shader is loaded with, it also defines how the edges are treated
+    fn game_of_life_processor(boundary: Boundary) -> Result<ShaderImportProcessor, ShaderError> {
+        Ok(match boundary {
+            Boundary::Wrap => Self::shader_processor()?,
+            Boundary::Clamp => Self::shader_processor()?.with_define("CLAMP_EDGES", ""),
+        })
+    }
+
+    fn load_game_of_life_shader(
+        context: &mut GlassContext,
+        rule: &dsl::Rule,
+        backend: dsl::Backend,
+        boundary: Boundary,
+    ) -> Result<wgpu::ShaderModule, ShaderError> {
+        Self::game_of_life_processor(boundary)?.load_shader_with_dsl(
+            context.device(),
+            GAME_OF_LIFE_SHADER,
+            rule,
+            backend,
+            Some("game_of_life_shader"),
+        )
+    }
+
+    /// Run `build` in a validation error scope, so a module or pipeline wgpu refuses is returned
+    /// as an error instead of panicking in the handler of uncaptured errors. naga only checks the
+    /// shader on its own, wgpu also checks it against the entry points and layout of the pipeline.
+    fn validated<T>(
+        context: &mut GlassContext,
+        build: impl FnOnce(&mut GlassContext) -> Result<T, ShaderError>,
+    ) -> Result<T, ShaderError> {
+        context
+            .device()
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        let built = build(context);
+        let error = pollster::block_on(context.device().pop_error_scope());
+
+        match (built, error) {
+            (Err(err), _) => Err(err),
+            (Ok(_), Some(wgpu::Error::Validation { description, .. })) => {
+                Err(ShaderError::Pipeline(description))
+            }
+            (Ok(_), Some(err)) => Err(ShaderError::Pipeline(err.to_string())),
+            (Ok(built), None) => Ok(built),
         }
     }
+
+    /// Build all pipelines, the update shader is generated from `rule` with `backend` and treats
+    /// the edges of the canvas as `boundary`. Fails if the rule or the shader generated from it is
+    /// invalid.
+    pub fn load(
+        context: &mut GlassContext,
+        rule: &dsl::Rule,
+        backend: dsl::Backend,
+        boundary: Boundary,
+    ) -> Result<Self, ShaderError> {
+        Self::validated(context, |context| {
+            let bg_layout = Self::create_bind_group_layout(context);
+            let game_of_life_shader =
+                Self::load_game_of_life_shader(context, rule, backend, boundary)?;
+
+            let draw_pipeline = Self::create_draw_pipeline(context)?;
+            let init_pipeline =
+                Self::create_init_pipeline(context, &bg_layout, &game_of_life_shader);
+            let game_of_life_pipeline =
+                Self::create_compute_pipeline(context, &bg_layout, &game_of_life_shader);
+
+            Ok(Self {
+                init_pipeline,
+                draw_pipeline,
+                game_of_life_pipeline,
+            })
+        })
+    }
+
+    /// Build only the update pipeline for a new rule, the other pipelines don't depend on the
+    /// rule and the textures they write to are left alone
+    pub fn load_update(
+        context: &mut GlassContext,
+        rule: &dsl::Rule,
+        backend: dsl::Backend,
+        boundary: Boundary,
+    ) -> Result<wgpu::ComputePipeline, ShaderError> {
+        Self::validated(context, |context| {
+            let bg_layout = Self::create_bind_group_layout(context);
+            let game_of_life_shader =
+                Self::load_game_of_life_shader(context, rule, backend, boundary)?;
+
+            Ok(Self::create_compute_pipeline(
+                context,
+                &bg_layout,
+                &game_of_life_shader,
+            ))
+        })
+    }
+
+    /// Build only the draw pipeline, for when its shader changed
+    pub fn load_draw(context: &mut GlassContext) -> Result<wgpu::ComputePipeline, ShaderError> {
+        Self::validated(context, Self::create_draw_pipeline)
+    }
+
+    /// Build the init and update pipelines, which share the shader generated from `rule`
+    pub fn load_game_of_life(
+        context: &mut GlassContext,
+        rule: &dsl::Rule,
+        backend: dsl::Backend,
+        boundary: Boundary,
+    ) -> Result<(wgpu::ComputePipeline, wgpu::ComputePipeline), ShaderError> {
+        Self::validated(context, |context| {
+            let bg_layout = Self::create_bind_group_layout(context);
+            let game_of_life_shader =
+                Self::load_game_of_life_shader(context, rule, backend, boundary)?;
+
+            Ok((
+                Self::create_init_pipeline(context, &bg_layout, &game_of_life_shader),
+                Self::create_compute_pipeline(context, &bg_layout, &game_of_life_shader),
+            ))
+        })
+    }
+
+    /// Every file each shader of the pipelines is composed from, by shader
+    pub fn shader_files(
+        boundary: Boundary,
+    ) -> Result<Vec<(&'static str, Vec<String>)>, ShaderError> {
+        Ok(vec![
+            (DRAW_SHADER, Self::shader_processor()?.files(DRAW_SHADER)?),
+            (
+                GAME_OF_LIFE_SHADER,
+                Self::game_of_life_processor(boundary)?.files(GAME_OF_LIFE_SHADER)?,
+            ),
+        ])
+    }
 }
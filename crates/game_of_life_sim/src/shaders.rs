@@ -1,6 +1,23 @@
-use crate::dsl;
+mod assets;
+mod error;
+mod preprocess;
+mod source_map;
+
+use std::collections::{HashMap, HashSet};
+
+pub use assets::{
+    default_source, AssetSource, EmbeddedAssets, FileSystemAssets, ASSET_DIR, EMBEDDED_ASSETS,
+};
+pub use error::{Diagnostic, Label, ShaderError};
 use glass::wgpu;
+use preprocess::{Conditionals, Directive};
 use regex::Regex;
+pub use source_map::{ComposedShader, Location, Origin, SourceMap};
+
+use crate::dsl;
+
+/// What the source map calls the code generated from a rule
+pub const GENERATED_RULE: &str = "generated rule";
 
 #[derive(Default, Debug)]
 pub struct ShaderImports {
@@ -8,32 +25,143 @@ pub struct ShaderImports {
     import_path: Option<String>,
 }
 
+/// What an `#import` line pulls in, either a file by its path relative to `assets` or a module by
+/// the name it declares with `#define_import_path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Import {
+    File(String),
+    /// `#import game::noise` or `#import game::noise::{randomFloat}`, the module is pasted in
+    /// whole and the listed items must be declared in it
+    Module {
+        name: String,
+        items: Vec<String>,
+    },
+}
+
+impl Import {
+    pub fn parse(import: &str) -> Self {
+        let import = import.trim();
+        if !import.contains("::") {
+            return Import::File(import.to_string());
+        }
+
+        let (name, items) = match import.split_once("::{") {
+            Some((name, items)) => (name, items.trim_end_matches('}')),
+            None => (import, ""),
+        };
+        Import::Module {
+            name: name.trim().to_string(),
+            items: items
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect(),
+        }
+    }
+}
+
 pub struct ShaderImportProcessor {
     import_custom_path_regex: Regex,
     define_import_path_regex: Regex,
+    /// The path of the file that defines each module, by module name
+    modules: HashMap<String, String>,
+    /// The defines every shader starts with, `#define` adds to them while composing
+    defines: HashMap<String, String>,
+    /// Where shaders and the files they import are read from
+    assets: Box<dyn AssetSource>,
+}
+
+/// The state of composing one shader out of its file and everything it imports
+struct Composition {
+    /// The path of the file being expanded on top of the paths of the files it is imported into
+    stack: Vec<String>,
+    included: HashSet<String>,
+    defines: HashMap<String, String>,
 }
 
 impl Default for ShaderImportProcessor {
+    /// A processor that reads from `default_source`
     fn default() -> Self {
+        Self::new(default_source())
+    }
+}
+
+impl ShaderImportProcessor {
+    /// A processor that reads shaders and their imports from `assets`, it knows no modules yet
+    pub fn new(assets: Box<dyn AssetSource>) -> Self {
         Self {
             import_custom_path_regex: Regex::new(r"^\s*#\s*import\s+(.+)").unwrap(),
             define_import_path_regex: Regex::new(r"^\s*#\s*define_import_path\s+(.+)").unwrap(),
+            modules: HashMap::new(),
+            defines: HashMap::new(),
+            assets,
+        }
+    }
+
+    /// A processor that reads from `default_source` and knows every module its files declare, see
+    /// `add_asset_modules`
+    pub fn with_asset_modules() -> Result<Self, ShaderError> {
+        let mut processor = Self::default();
+        processor.add_asset_modules()?;
+        Ok(processor)
+    }
+
+    /// Register every `.wgsl` file of the assets that declares a module with
+    /// `#define_import_path`, so shaders can import it by name. Sources that can't list their
+    /// files have no modules to find.
+    pub fn add_asset_modules(&mut self) -> Result<(), ShaderError> {
+        let files = self
+            .assets
+            .files()
+            .map_err(|source| ShaderError::MissingFile {
+                path: ASSET_DIR.to_string(),
+                source,
+            })?;
+        for shader_path in files.iter().filter(|path| path.ends_with(".wgsl")) {
+            let source = self.read(shader_path)?;
+            if self.get_imports_from_str(&source).import_path.is_some() {
+                self.add_module(shader_path, &source)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Define `name` for `#ifdef` and `#ifndef` and replace `#{name}` in the shaders with `value`
+    pub fn with_define(mut self, name: &str, value: impl ToString) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Register the module `source` declares with `#define_import_path`, imports of the module
+    /// read it from `shader_path`
+    pub fn add_module(&mut self, shader_path: &str, source: &str) -> Result<(), ShaderError> {
+        let Some(name) = self.get_imports_from_str(source).import_path else {
+            return Err(ShaderError::NotAModule(shader_path.to_string()));
+        };
+
+        match self.modules.get(&name) {
+            Some(path) if path != shader_path => Err(ShaderError::DuplicateModule {
+                paths: (path.clone(), shader_path.to_string()),
+                name,
+            }),
+            _ => {
+                self.modules.insert(name, shader_path.to_string());
+                Ok(())
+            }
         }
     }
-}
 
-impl ShaderImportProcessor {
     pub fn load_shader(
         &self,
         device: &wgpu::Device,
         shader_path: &str,
         label: Option<&str>,
-    ) -> std::io::Result<wgpu::ShaderModule> {
-        let shader = self.load_shader_inner(shader_path)?;
+    ) -> Result<wgpu::ShaderModule, ShaderError> {
+        let shader = self.load_source(shader_path)?;
 
         Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label,
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(&shader)),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(&shader.source)),
         }))
     }
 
@@ -41,27 +169,73 @@ impl ShaderImportProcessor {
         &self,
         device: &wgpu::Device,
         shader_path: &str,
-        dsl: &dsl::Statement,
+        rule: &dsl::Rule,
+        backend: dsl::Backend,
         label: Option<&str>,
-    ) -> std::io::Result<wgpu::ShaderModule> {
-        let root = format!("{}/assets", env!("CARGO_MANIFEST_DIR"));
-        let shader_contents = self.load_shader_inner(shader_path)?;
-
-        // Replace base shader with the shader rules
-        let shader_rules = dsl.to_shader();
-        let shader = shader_contents.replace("{PLACEHOLDER}", &shader_rules);
-
-        if std::env::var("DEBUG_SHADER").is_ok() {
-            std::fs::write(format!("{root}/{shader_path}.debug.wgsl"), shader.clone())
-                .expect("Failed to write shader file");
-        }
+    ) -> Result<wgpu::ShaderModule, ShaderError> {
+        let shader = self.load_source_with_dsl(shader_path, rule, backend)?;
 
         Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label,
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(&shader)),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(&shader.source)),
         }))
     }
 
+    /// Compose and validate the wgsl `load_shader` builds its module from, no device needed
+    pub fn load_source(&self, shader_path: &str) -> Result<ComposedShader, ShaderError> {
+        let shader = self.load_shader_inner(shader_path)?;
+        check_placeholders(&shader)?;
+        validate_wgsl(&shader.source).map_err(|err| err.locate(&shader))?;
+        Ok(shader)
+    }
+
+    /// Compose the shader, fill in the code generated from `rule` with `backend` and validate the
+    /// result, this is the wgsl `load_shader_with_dsl` builds its module from
+    pub fn load_source_with_dsl(
+        &self,
+        shader_path: &str,
+        rule: &dsl::Rule,
+        backend: dsl::Backend,
+    ) -> Result<ComposedShader, ShaderError> {
+        check_rule(rule)?;
+
+        let rule = rule.optimize();
+        let mut defines = self.defines.clone();
+        if rule.reads_neighbor_mask() {
+            defines.insert("NEIGHBOR_MASK".to_string(), String::new());
+        }
+        let mut shader = self.compose_with(shader_path, defines)?;
+
+        let (declarations, statement) = match backend.for_rule(&rule) {
+            dsl::Backend::Expression => (String::new(), rule.statement.to_shader()),
+            dsl::Backend::InlineTable => {
+                let table = dsl::RuleTable::build(&rule).unwrap();
+                (table.to_shader(), table.lookup_shader())
+            }
+            dsl::Backend::StorageTable => (String::new(), dsl::RuleTable::storage_lookup_shader()),
+        };
+
+        // Replace base shader with the shader rules and the neighborhood they count in
+        shader.replace("{STATES}", &rule.states.to_string(), GENERATED_RULE);
+        shader.replace("{DECLARATIONS}", &declarations, GENERATED_RULE);
+        shader.replace(
+            "{NEIGHBORHOOD}",
+            &rule.neighborhood.to_shader(),
+            GENERATED_RULE,
+        );
+        shader.replace("{PLACEHOLDER}", &statement, GENERATED_RULE);
+
+        // Catch mistakes in generated code here with a readable message, wgpu would only panic
+        check_placeholders(&shader)?;
+        validate_wgsl(&shader.source).map_err(|err| {
+            let err = err.locate(&shader);
+            log::error!("Generated shader {shader_path} is invalid:\n{err}");
+            err
+        })?;
+
+        Ok(shader)
+    }
+
     pub fn get_imports_from_str(&self, shader: &str) -> ShaderImports {
         let mut shader_imports = ShaderImports::default();
         for line in shader.lines() {
@@ -70,44 +244,376 @@ impl ShaderImportProcessor {
                 shader_imports.imports.push(import.as_str().to_string());
             } else if let Some(cap) = self.define_import_path_regex.captures(line) {
                 let path = cap.get(1).unwrap();
-                shader_imports.import_path = Some(path.as_str().to_string());
+                shader_imports.import_path = Some(path.as_str().trim().to_string());
             }
         }
 
         shader_imports
     }
 
-    fn load_shader_inner(&self, shader_path: &str) -> std::io::Result<String> {
-        let root = format!("{}/assets", env!("CARGO_MANIFEST_DIR"));
-        let mut shader_contents = match std::fs::read_to_string(format!("{root}/{shader_path}")) {
-            Ok(contents) => contents,
-            Err(err) => {
-                log::error!("Failed to read shader file: {}", shader_path);
-                return Err(err);
-            }
+    /// Every file `shader_path` is composed from, itself first, as paths relative to `assets`
+    pub fn files(&self, shader_path: &str) -> Result<Vec<String>, ShaderError> {
+        Ok(self.load_shader_inner(shader_path)?.files)
+    }
+
+    fn load_shader_inner(&self, shader_path: &str) -> Result<ComposedShader, ShaderError> {
+        self.compose(shader_path)
+    }
+
+    /// Read `shader_path` from the assets and replace its imports with the contents of the imported
+    /// files and modules, recursively. Every file is pasted in once where it is first imported
+    /// and later imports of it are dropped. An import cycle is an error that names every file in
+    /// it. Lines are kept or dropped by `#ifdef`, `#ifndef` and `#else` blocks, which can also
+    /// surround imports, and `#{NAME}` is replaced by the value of the define. The source map of
+    /// the result points each line back at the file and line it came from.
+    pub fn compose(&self, shader_path: &str) -> Result<ComposedShader, ShaderError> {
+        self.compose_with(shader_path, self.defines.clone())
+    }
+
+    /// Compose `shader_path` like `compose` does, starting from `defines` instead of the defines
+    /// of the processor
+    fn compose_with(
+        &self,
+        shader_path: &str,
+        defines: HashMap<String, String>,
+    ) -> Result<ComposedShader, ShaderError> {
+        let shader_contents = self.read(shader_path)?;
+
+        let mut composition = Composition {
+            stack: vec![shader_path.to_string()],
+            included: HashSet::from([shader_path.to_string()]),
+            defines,
         };
+        self.expand_imports(&shader_contents, &mut composition)
+    }
 
-        // Replace all imports with the contents of the imported file
-        let imports = self.get_imports_from_str(shader_contents.as_str());
-        imports.imports.iter().for_each(|import| {
-            let import_path = match &imports.import_path {
-                Some(path) => format!("{root}/{path}"),
-                None => root.to_string(),
-            };
+    /// Expand the directives and imports of the file on top of the stack of `composition`
+    fn expand_imports(
+        &self,
+        shader_contents: &str,
+        composition: &mut Composition,
+    ) -> Result<ComposedShader, ShaderError> {
+        let path = composition.stack.last().unwrap().clone();
+        let invalid = |line: usize, message: String| ShaderError::Preprocessor {
+            path: path.clone(),
+            line: line + 1,
+            message,
+        };
 
-            let import_contents =
-                match std::fs::read_to_string(format!("{}/{}", import_path, import)) {
-                    Ok(contents) => contents,
-                    Err(err) => {
-                        log::error!("Failed to read import file: {} {}", import, err);
-                        std::process::exit(1);
+        let mut conditionals = Conditionals::default();
+        let mut composed = ComposedShader::default();
+        composed.files.push(path.clone());
+        for (number, line) in shader_contents.lines().enumerate() {
+            if let Some(directive) = Directive::parse(line) {
+                conditionals
+                    .apply(&directive, number, &composition.defines)
+                    .map_err(|message| invalid(number, message))?;
+                if let (Directive::Define(name, value), true) =
+                    (directive, conditionals.is_active())
+                {
+                    composition.defines.insert(name, value);
+                }
+                continue;
+            }
+            // Module names only mean something to the processor, wgsl has no such directive
+            if !conditionals.is_active() || self.define_import_path_regex.is_match(line) {
+                continue;
+            }
+            let Some(cap) = self.import_custom_path_regex.captures(line) else {
+                let line = preprocess::substitute(line, &composition.defines).map_err(|name| {
+                    ShaderError::UnresolvedPlaceholder {
+                        path: path.clone(),
+                        line: number + 1,
+                        name,
                     }
-                };
+                })?;
+                composed.push_line(
+                    &line,
+                    Origin::File {
+                        path: path.clone(),
+                        line: number + 1,
+                    },
+                );
+                continue;
+            };
+
+            let import = cap.get(1).unwrap().as_str().trim();
+            let (import_path, items) = match Import::parse(import) {
+                Import::File(path) => (path, Vec::new()),
+                Import::Module { name, items } => match self.modules.get(&name) {
+                    Some(path) => (path.clone(), items),
+                    None => return Err(ShaderError::UnknownModule(name)),
+                },
+            };
+
+            if let Some(start) = composition
+                .stack
+                .iter()
+                .position(|path| *path == import_path)
+            {
+                let mut cycle = composition.stack[start..].to_vec();
+                cycle.push(import_path);
+                return Err(ShaderError::ImportCycle(cycle));
+            }
+
+            let import_contents = self.read(&import_path)?;
+            if let Some(item) = items.iter().find(|item| !declares(&import_contents, item)) {
+                return Err(ShaderError::MissingItem {
+                    path: import_path,
+                    item: item.clone(),
+                });
+            }
+            if !composition.included.insert(import_path.clone()) {
+                continue;
+            }
+
+            composition.stack.push(import_path);
+            composed.append(self.expand_imports(&import_contents, composition)?);
+            composition.stack.pop();
+        }
+
+        conditionals
+            .finish()
+            .map_err(|(line, message)| invalid(line, message))?;
+        Ok(composed)
+    }
+
+    /// Read a shader or a file it imports from the assets
+    fn read(&self, shader_path: &str) -> Result<String, ShaderError> {
+        self.assets.read(shader_path).map_err(|source| {
+            log::error!("Failed to read shader file: {shader_path} {source}");
+            ShaderError::MissingFile {
+                path: shader_path.to_string(),
+                source,
+            }
+        })
+    }
+}
+
+/// Whether wgsl `source` declares a function, type, constant or variable named `item`
+fn declares(source: &str, item: &str) -> bool {
+    let declaration = format!(
+        r"\b(fn|struct|alias|const|override|var(<[^>]*>)?)\s+{}\b",
+        regex::escape(item)
+    );
+    Regex::new(&declaration).unwrap().is_match(source)
+}
+
+/// Find a `{PLACEHOLDER}` of the shader templates that is left in the composed shader
+fn check_placeholders(shader: &ComposedShader) -> Result<(), ShaderError> {
+    let placeholder = Regex::new(r"\{[A-Z_]+\}").unwrap();
+    for (number, line) in shader.source.lines().enumerate() {
+        if let Some(name) = placeholder.find(line) {
+            let (path, line) = match shader.source_map.origin(number) {
+                Some(Origin::File { path, line }) => (path.clone(), *line),
+                Some(Origin::Generated { name, line }) => (name.clone(), *line),
+                None => (String::new(), number + 1),
+            };
+            return Err(ShaderError::UnresolvedPlaceholder {
+                path,
+                line,
+                name: name.as_str().to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check a rule before a shader is built from it or its table is uploaded
+pub fn check_rule(rule: &dsl::Rule) -> Result<(), ShaderError> {
+    rule.check().map_err(ShaderError::InvalidRule)
+}
+
+/// Parse and validate wgsl with naga, returning the diagnostic pointing at the offending source
+/// when it is invalid
+#[cfg(feature = "validate")]
+pub fn validate_wgsl(source: &str) -> Result<(), ShaderError> {
+    use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+    let module = naga::front::wgsl::parse_str(source).map_err(|err| {
+        ShaderError::Parse(Diagnostic {
+            message: err.message().to_string(),
+            labels: err
+                .labels()
+                .filter_map(|(span, label)| Some(Label::new(span.to_range()?, label)))
+                .collect(),
+            rendered: err.emit_to_string(source),
+        })
+    })?;
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|err| {
+            ShaderError::Validation(Diagnostic {
+                message: err.as_inner().to_string(),
+                labels: err
+                    .spans()
+                    .filter_map(|(span, label)| Some(Label::new(span.to_range()?, label)))
+                    .collect(),
+                rendered: err.emit_to_string(source),
+            })
+        })?;
+
+    Ok(())
+}
+
+/// Without the `validate` feature shaders are only checked when wgpu creates them
+#[cfg(not(feature = "validate"))]
+pub fn validate_wgsl(_source: &str) -> Result<(), ShaderError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dsl::rulesets, pipelines::GAME_OF_LIFE_SHADER};
+
+    /// A processor that reads the shaders from `files`, by path
+    fn processor(files: &'static [(&'static str, &'static str)]) -> ShaderImportProcessor {
+        ShaderImportProcessor::new(Box::new(move |path: &str| {
+            files
+                .iter()
+                .find(|(file, _)| *file == path)
+                .map(|(_, contents)| contents.to_string())
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, path))
+        }))
+    }
+
+    fn lines(shader: &ComposedShader) -> Vec<&str> {
+        shader.source.lines().collect()
+    }
+
+    #[test]
+    fn asset_modules_are_found() {
+        let processor = ShaderImportProcessor::with_asset_modules().unwrap();
+        assert_eq!(
+            processor
+                .modules
+                .get("game_of_life::core")
+                .map(String::as_str),
+            Some("core.wgsl")
+        );
+        assert_eq!(processor.modules.len(), 1);
+
+        let mut processor = ShaderImportProcessor::new(Box::new(EmbeddedAssets::new(&[
+            ("main.wgsl", "#import noise::{hash}\nfn main() {}"),
+            ("lib/noise.wgsl", "#define_import_path noise\nfn hash() {}"),
+            ("noise.ron", "#define_import_path ron"),
+        ])));
+        processor.add_asset_modules().unwrap();
+        assert_eq!(processor.modules.len(), 1);
+        assert_eq!(
+            lines(&processor.compose("main.wgsl").unwrap()),
+            ["fn hash() {}", "fn main() {}"]
+        );
+    }
+
+    #[test]
+    fn nested_imports_are_expanded() {
+        let shader = processor(&[
+            ("a.wgsl", "#import b.wgsl\nfn a() {}"),
+            ("b.wgsl", "#import c.wgsl\nfn b() {}"),
+            ("c.wgsl", "fn c() {}"),
+        ])
+        .compose("a.wgsl")
+        .unwrap();
+        assert_eq!(lines(&shader), ["fn c() {}", "fn b() {}", "fn a() {}"]);
+        assert_eq!(shader.files, ["a.wgsl", "b.wgsl", "c.wgsl"]);
+    }
+
+    #[test]
+    fn diamond_imports_are_included_once() {
+        let shader = processor(&[
+            ("a.wgsl", "#import b.wgsl\n#import c.wgsl\nfn a() {}"),
+            ("b.wgsl", "#import d.wgsl\nfn b() {}"),
+            ("c.wgsl", "#import d.wgsl\nfn c() {}"),
+            ("d.wgsl", "fn d() {}"),
+        ])
+        .compose("a.wgsl")
+        .unwrap();
+        assert_eq!(
+            lines(&shader),
+            ["fn d() {}", "fn b() {}", "fn c() {}", "fn a() {}"]
+        );
+        assert_eq!(shader.files, ["a.wgsl", "b.wgsl", "d.wgsl", "c.wgsl"]);
+    }
+
+    #[test]
+    fn import_cycles_are_named() {
+        let err = processor(&[
+            ("a.wgsl", "#import b.wgsl\nfn a() {}"),
+            ("b.wgsl", "#import a.wgsl\nfn b() {}"),
+        ])
+        .compose("a.wgsl")
+        .unwrap_err();
+        assert_eq!(err.to_string(), "import cycle: a.wgsl -> b.wgsl -> a.wgsl");
+    }
+
+    #[test]
+    fn directives_apply_across_imports() {
+        let shader = processor(&[
+            (
+                "a.wgsl",
+                "#define LOCAL\n#ifdef LOCAL\n#import b.wgsl\n#endif\nfn a() -> u32 { return #{SIZE}u; }",
+            ),
+            (
+                "b.wgsl",
+                "#ifdef MISSING\n#define SIZE 2\n#else\n#define SIZE 8\n#endif\nfn b() {}",
+            ),
+        ])
+        .compose("a.wgsl")
+        .unwrap();
+        assert_eq!(
+            lines(&shader),
+            ["fn b() {}", "fn a() -> u32 { return 8u; }"]
+        );
+
+        let err = processor(&[("a.wgsl", "fn a() {}\nlet x = #{SIZE};")])
+            .compose("a.wgsl")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "a.wgsl:2: nothing to replace #{SIZE} with");
+    }
+
+    #[test]
+    fn unclosed_blocks_point_at_where_they_open() {
+        let err = processor(&[(
+            "a.wgsl",
+            "#ifdef A\n#endif\n#ifndef B\nfn a() {}\n#ifdef C\n#endif\nfn b() {}",
+        )])
+        .compose("a.wgsl")
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a.wgsl:3: 1 #ifdef or #ifndef without #endif"
+        );
+
+        let err = processor(&[("a.wgsl", "fn a() {}\n#endif")])
+            .compose("a.wgsl")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a.wgsl:2: #endif without #ifdef or #ifndef"
+        );
+    }
+
+    fn game_of_life_source(rule: &dsl::Rule) -> String {
+        ShaderImportProcessor::with_asset_modules()
+            .unwrap()
+            .with_define("WORKGROUP_SIZE", 8)
+            .load_source_with_dsl(GAME_OF_LIFE_SHADER, rule, dsl::Backend::Expression)
+            .unwrap()
+            .source
+    }
 
-            let import_string: String = format!("#import {import}");
-            shader_contents = shader_contents.replace(&import_string, import_contents.as_str());
-        });
+    #[test]
+    fn only_rules_that_read_it_build_the_neighbor_mask() {
+        let conway = dsl::Rule::from(rulesets::conways_game_of_life());
+        assert!(!conway.optimize().reads_neighbor_mask());
+        assert!(!game_of_life_source(&conway).contains("neighbor_mask(location)"));
 
-        Ok(shader_contents)
+        let hensel = dsl::parse_rulestring("B2-a/S12").unwrap();
+        assert!(hensel.optimize().reads_neighbor_mask());
+        assert!(
+            game_of_life_source(&hensel).contains("let neighbor_mask = neighbor_mask(location);")
+        );
     }
 }
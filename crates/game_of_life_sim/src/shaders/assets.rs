@@ -0,0 +1,132 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The directory shaders are read from by `FileSystemAssets`
+pub const ASSET_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets");
+
+// `EMBEDDED_ASSETS`, the path and contents of every shader and rule in `assets`, written by the
+// build script
+include!(concat!(env!("OUT_DIR"), "/embedded_assets.rs"));
+
+/// Where shaders, the files they import and the rule library are read from, by their path
+/// relative to `assets`
+pub trait AssetSource {
+    fn read(&self, path: &str) -> io::Result<String>;
+
+    /// The paths of every file that can be read, sorted. Sources that can't list their files, like
+    /// closures, have none.
+    fn files(&self) -> io::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+}
+
+/// Reads shaders from a directory at runtime, edits show up without rebuilding
+#[derive(Debug, Clone)]
+pub struct FileSystemAssets {
+    root: PathBuf,
+}
+
+impl FileSystemAssets {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl Default for FileSystemAssets {
+    /// The `assets` directory of the crate
+    fn default() -> Self {
+        Self::new(ASSET_DIR)
+    }
+}
+
+impl AssetSource for FileSystemAssets {
+    fn read(&self, path: &str) -> io::Result<String> {
+        std::fs::read_to_string(self.root.join(path))
+    }
+
+    fn files(&self) -> io::Result<Vec<String>> {
+        let mut files = Vec::new();
+        collect_files(&self.root, &self.root, &mut files)?;
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// Collect the paths of the files in `dir` and its subdirectories relative to `root`, with `/`
+/// between directories on every platform
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    Ok(())
+}
+
+/// Reads the shaders and rules the build script baked into the binary, works without a filesystem
+/// and wherever the binary is moved
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedAssets {
+    files: &'static [(&'static str, &'static str)],
+}
+
+impl EmbeddedAssets {
+    pub fn new(files: &'static [(&'static str, &'static str)]) -> Self {
+        Self { files }
+    }
+}
+
+impl Default for EmbeddedAssets {
+    /// Every shader and rule in `assets` when the crate was built
+    fn default() -> Self {
+        Self::new(EMBEDDED_ASSETS)
+    }
+}
+
+impl AssetSource for EmbeddedAssets {
+    fn read(&self, path: &str) -> io::Result<String> {
+        self.files
+            .iter()
+            .find(|(file, _)| *file == path)
+            .map(|(_, contents)| contents.to_string())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{path} is not embedded in the binary"),
+                )
+            })
+    }
+
+    fn files(&self) -> io::Result<Vec<String>> {
+        Ok(self
+            .files
+            .iter()
+            .map(|(file, _)| file.to_string())
+            .collect())
+    }
+}
+
+/// Files given as closures, e.g. to compose shaders from sources held in memory
+impl<F: Fn(&str) -> io::Result<String>> AssetSource for F {
+    fn read(&self, path: &str) -> io::Result<String> {
+        self(path)
+    }
+}
+
+/// The source shaders and rules are loaded from, embedded with the `embedded-assets` feature and
+/// on wasm where there is no filesystem. Hot reload watches the files, so it reads them from disk.
+pub fn default_source() -> Box<dyn AssetSource> {
+    if cfg!(any(
+        target_arch = "wasm32",
+        all(feature = "embedded-assets", not(feature = "hot-reload"))
+    )) {
+        Box::new(EmbeddedAssets::default())
+    } else {
+        Box::new(FileSystemAssets::default())
+    }
+}
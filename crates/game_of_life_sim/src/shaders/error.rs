@@ -0,0 +1,301 @@
+use std::{fmt, ops::Range};
+
+use super::source_map::{ComposedShader, Location};
+use crate::dsl;
+
+/// A part of the composed source a diagnostic points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    /// Byte range in the composed source
+    pub range: Range<usize>,
+    /// What is wrong there
+    pub message: String,
+    /// Where the range starts in the file it was composed from, once the diagnostic is located
+    pub location: Option<Location>,
+}
+
+impl Label {
+    /// A label that is not located yet
+    pub fn new(range: Range<usize>, message: impl ToString) -> Self {
+        Self {
+            range,
+            message: message.to_string(),
+            location: None,
+        }
+    }
+}
+
+/// A problem reported by naga, with the parts of the composed source it points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub labels: Vec<Label>,
+    /// The message rendered with the lines of source it points at
+    pub rendered: String,
+}
+
+impl Diagnostic {
+    /// Point the labels at the files and generated code `shader` was composed from, and render
+    /// the message again with those locations instead of the lines of the composed source
+    pub fn locate(&mut self, shader: &ComposedShader) {
+        // Notes are rendered after the source lines and don't refer to them
+        let notes = self
+            .rendered
+            .lines()
+            .filter(|line| line.trim_start().starts_with("= "))
+            .map(|line| line.trim().to_string())
+            .collect::<Vec<_>>();
+
+        let mut rendered = format!("error: {}\n", self.message);
+        for label in &mut self.labels {
+            label.location = shader.source_map.locate(&shader.source, label.range.start);
+            let Some(location) = &label.location else {
+                continue;
+            };
+
+            let line_start = shader.source[..label.range.start]
+                .rfind('\n')
+                .map_or(0, |newline| newline + 1);
+            let line = shader.source[line_start..]
+                .lines()
+                .next()
+                .unwrap_or_default();
+            let width = label
+                .range
+                .len()
+                .min(line.len() + line_start - label.range.start);
+            rendered.push_str(&format!(
+                "  ┌─ {location}\n  │ {line}\n  │ {}{} {}\n",
+                " ".repeat(location.column - 1),
+                "^".repeat(width.max(1)),
+                label.message
+            ));
+        }
+        for note in notes {
+            rendered.push_str(&format!("  {note}\n"));
+        }
+        self.rendered = rendered;
+    }
+}
+
+/// Everything that can go wrong while composing, generating or validating a shader
+#[derive(Debug)]
+pub enum ShaderError {
+    /// A shader or a file it imports could not be read
+    MissingFile {
+        path: String,
+        source: std::io::Error,
+    },
+    /// Files that import each other, the first file is repeated at the end
+    ImportCycle(Vec<String>),
+    /// `#import` of a module no registered file declares
+    UnknownModule(String),
+    /// An item listed in a module import that the module does not declare
+    MissingItem {
+        path: String,
+        item: String,
+    },
+    /// A file registered as a module that does not declare one with `#define_import_path`
+    NotAModule(String),
+    /// Two files that declare the same module
+    DuplicateModule {
+        name: String,
+        paths: (String, String),
+    },
+    /// A malformed or unbalanced `#ifdef`, `#ifndef`, `#else` or `#endif`
+    Preprocessor {
+        path: String,
+        line: usize,
+        message: String,
+    },
+    /// A `#{NAME}` without a define or a `{PLACEHOLDER}` the generator did not fill in
+    UnresolvedPlaceholder {
+        path: String,
+        line: usize,
+        name: String,
+    },
+    /// The rule the shader is generated from does not pass `Rule::check`
+    InvalidRule(Vec<dsl::CheckError>),
+    Parse(Diagnostic),
+    Validation(Diagnostic),
+    /// wgpu refused a module or pipeline that naga accepted, like an entry point the pipeline
+    /// names that the shader lacks or bindings that don't match the pipeline layout
+    Pipeline(String),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::MissingFile { path, source } => {
+                write!(f, "failed to read shader file {path}: {source}")
+            }
+            ShaderError::ImportCycle(cycle) => write!(f, "import cycle: {}", cycle.join(" -> ")),
+            ShaderError::UnknownModule(name) => {
+                write!(f, "unknown module {name}, no registered file declares it")
+            }
+            ShaderError::MissingItem { path, item } => {
+                write!(f, "{path} does not declare the imported item {item}")
+            }
+            ShaderError::NotAModule(path) => {
+                write!(
+                    f,
+                    "{path} does not declare a module with #define_import_path"
+                )
+            }
+            ShaderError::DuplicateModule { name, paths } => write!(
+                f,
+                "module {name} is declared by both {} and {}",
+                paths.0, paths.1
+            ),
+            ShaderError::Preprocessor {
+                path,
+                line,
+                message,
+            } => write!(f, "{path}:{line}: {message}"),
+            ShaderError::UnresolvedPlaceholder { path, line, name } => {
+                write!(f, "{path}:{line}: nothing to replace {name} with")
+            }
+            ShaderError::InvalidRule(errors) => {
+                let errors = errors.iter().map(|err| err.to_string()).collect::<Vec<_>>();
+                write!(f, "invalid rule: {}", errors.join(", "))
+            }
+            ShaderError::Parse(diagnostic) | ShaderError::Validation(diagnostic) => {
+                write!(f, "{}", diagnostic.rendered)
+            }
+            ShaderError::Pipeline(message) => write!(f, "failed to build the pipeline: {message}"),
+        }
+    }
+}
+
+impl ShaderError {
+    /// The error on one line, parse and validation errors start with where they point at
+    pub fn summary(&self) -> String {
+        match self {
+            ShaderError::Parse(diagnostic) | ShaderError::Validation(diagnostic) => {
+                match diagnostic
+                    .labels
+                    .iter()
+                    .find_map(|label| label.location.as_ref())
+                {
+                    Some(location) => format!("{location}: {}", diagnostic.message),
+                    None => diagnostic.message.clone(),
+                }
+            }
+            // wgpu spreads its errors and their causes over indented lines
+            ShaderError::Pipeline(message) => message
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join(": "),
+            err => err.to_string(),
+        }
+    }
+
+    /// Point a parse or validation error of the composed `shader` at the files and generated code
+    /// it was composed from
+    pub fn locate(mut self, shader: &ComposedShader) -> Self {
+        if let ShaderError::Parse(diagnostic) | ShaderError::Validation(diagnostic) = &mut self {
+            diagnostic.locate(shader);
+        }
+        self
+    }
+}
+
+impl std::error::Error for ShaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShaderError::MissingFile { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "validate"))]
+mod tests {
+    use super::*;
+    use crate::shaders::{source_map::Origin, validate_wgsl};
+
+    /// A shader that imports `core.wgsl`, which is pasted in before the lines of `main.wgsl`
+    fn importing_core(core: &[&str]) -> ComposedShader {
+        let mut shader = ComposedShader::default();
+        for (index, line) in core.iter().enumerate() {
+            let origin = Origin::File {
+                path: "core.wgsl".to_string(),
+                line: index + 1,
+            };
+            shader.push_line(line, origin);
+        }
+        let main = [
+            "@compute @workgroup_size(1)",
+            "fn main() {",
+            "    let a = f();",
+            "}",
+        ];
+        for (index, line) in main.iter().enumerate() {
+            let origin = Origin::File {
+                path: "main.wgsl".to_string(),
+                line: index + 2,
+            };
+            shader.push_line(line, origin);
+        }
+        shader
+    }
+
+    #[test]
+    fn errors_in_imported_files_point_at_them() {
+        let shader = importing_core(&["fn f() -> u32 {", "    return missing;", "}"]);
+        let err = validate_wgsl(&shader.source).unwrap_err().locate(&shader);
+        let ShaderError::Parse(diagnostic) = &err else {
+            panic!("{err:?}");
+        };
+        assert_eq!(
+            diagnostic.labels[0].location.as_ref().unwrap().to_string(),
+            "core.wgsl:2:12"
+        );
+        assert!(
+            err.summary().starts_with("core.wgsl:2:12: "),
+            "{}",
+            err.summary()
+        );
+        assert!(
+            err.to_string()
+                .contains("┌─ core.wgsl:2:12\n  │     return missing;"),
+            "{err}"
+        );
+
+        let shader = importing_core(&["fn f() -> u32 {", "    return 1.0;", "}"]);
+        let err = validate_wgsl(&shader.source).unwrap_err().locate(&shader);
+        let ShaderError::Validation(diagnostic) = &err else {
+            panic!("{err:?}");
+        };
+        let locations = diagnostic
+            .labels
+            .iter()
+            .filter_map(|label| label.location.as_ref())
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert!(!locations.is_empty());
+        assert!(
+            locations
+                .iter()
+                .all(|location| location.starts_with("core.wgsl:")),
+            "{locations:?}"
+        );
+    }
+
+    #[test]
+    fn pipeline_errors_are_summarized_on_one_line() {
+        let err = ShaderError::Pipeline(
+            "In Device::create_compute_pipeline\n    \
+             note: label = `Update Pipeline`\n    \
+             Unable to find entry point 'update'\n"
+                .to_string(),
+        );
+        assert_eq!(
+            err.summary(),
+            "In Device::create_compute_pipeline: note: label = `Update Pipeline`: \
+             Unable to find entry point 'update'"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use regex::{Captures, Regex};
+
+/// A preprocessor directive on a line of its own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Directive {
+    /// `#define NAME value`, the value may be left out to only define the name
+    Define(String, String),
+    IfDef(String),
+    IfNDef(String),
+    Else,
+    EndIf,
+}
+
+impl Directive {
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let rest = line.strip_prefix('#')?.trim_start();
+        let (keyword, argument) = match rest.split_once(char::is_whitespace) {
+            Some((keyword, argument)) => (keyword, argument.trim()),
+            None => (rest, ""),
+        };
+
+        match keyword {
+            "define" => {
+                let (name, value) = argument
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((argument, ""));
+                Some(Directive::Define(
+                    name.to_string(),
+                    value.trim().to_string(),
+                ))
+            }
+            "ifdef" => Some(Directive::IfDef(argument.to_string())),
+            "ifndef" => Some(Directive::IfNDef(argument.to_string())),
+            "else" => Some(Directive::Else),
+            "endif" => Some(Directive::EndIf),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    /// The line of the `#ifdef` or `#ifndef` that opened the block
+    line: usize,
+    /// Whether the lines around the block are kept
+    outer: bool,
+    condition: bool,
+    in_else: bool,
+}
+
+/// The `#ifdef` blocks around the current line of a file
+#[derive(Debug, Default)]
+pub struct Conditionals {
+    blocks: Vec<Block>,
+}
+
+impl Conditionals {
+    /// Whether the current line is kept, every enclosing block takes the branch it is in
+    pub fn is_active(&self) -> bool {
+        self.blocks
+            .last()
+            .is_none_or(|block| block.outer && block.condition != block.in_else)
+    }
+
+    /// Apply a conditional directive on `line`, `#define` is left to the caller
+    pub fn apply(
+        &mut self,
+        directive: &Directive,
+        line: usize,
+        defines: &HashMap<String, String>,
+    ) -> Result<(), String> {
+        let outer = self.is_active();
+        match directive {
+            Directive::IfDef(name) | Directive::IfNDef(name) if name.is_empty() => {
+                return Err("expected the name of a define".to_string())
+            }
+            Directive::IfDef(name) => self.blocks.push(Block {
+                line,
+                outer,
+                condition: defines.contains_key(name),
+                in_else: false,
+            }),
+            Directive::IfNDef(name) => self.blocks.push(Block {
+                line,
+                outer,
+                condition: !defines.contains_key(name),
+                in_else: false,
+            }),
+            Directive::Else => match self.blocks.last_mut() {
+                Some(block) if !block.in_else => block.in_else = true,
+                Some(_) => return Err("#else appears twice in the same block".to_string()),
+                None => return Err("#else without #ifdef or #ifndef".to_string()),
+            },
+            Directive::EndIf => {
+                if self.blocks.pop().is_none() {
+                    return Err("#endif without #ifdef or #ifndef".to_string());
+                }
+            }
+            Directive::Define(..) => {}
+        }
+        Ok(())
+    }
+
+    /// Check that every block was closed at the end of a file, the error is on the line that
+    /// opened the innermost block left open
+    pub fn finish(&self) -> Result<(), (usize, String)> {
+        match self.blocks.last() {
+            None => Ok(()),
+            Some(block) => Err((
+                block.line,
+                format!("{} #ifdef or #ifndef without #endif", self.blocks.len()),
+            )),
+        }
+    }
+}
+
+/// Replace every `#{NAME}` in `line` with the value of the define, returns the first placeholder
+/// with an undefined name as the error
+pub fn substitute(line: &str, defines: &HashMap<String, String>) -> Result<String, String> {
+    if !line.contains("#{") {
+        return Ok(line.to_string());
+    }
+
+    let placeholder = Regex::new(r"#\{\s*(\w+)\s*\}").unwrap();
+    let mut undefined = None;
+    let line = placeholder.replace_all(line, |cap: &Captures| {
+        let name = &cap[1];
+        defines.get(name).cloned().unwrap_or_else(|| {
+            undefined.get_or_insert_with(|| name.to_string());
+            String::new()
+        })
+    });
+
+    match undefined {
+        Some(name) => Err(format!("#{{{name}}}")),
+        None => Ok(line.into_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defines(names: &[&str]) -> HashMap<String, String> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), String::new()))
+            .collect()
+    }
+
+    /// Whether each line of `lines` is kept, the directives count as not kept
+    fn kept(lines: &[&str], defines: &HashMap<String, String>) -> Result<Vec<bool>, String> {
+        let mut conditionals = Conditionals::default();
+        let mut kept = Vec::new();
+        for (number, line) in lines.iter().enumerate() {
+            match Directive::parse(line) {
+                Some(directive) => {
+                    conditionals.apply(&directive, number, defines)?;
+                    kept.push(false);
+                }
+                None => kept.push(conditionals.is_active()),
+            }
+        }
+        conditionals.finish().map_err(|(_, message)| message)?;
+        Ok(kept)
+    }
+
+    #[test]
+    fn directives_parse() {
+        assert_eq!(
+            Directive::parse("#define SIZE 8"),
+            Some(Directive::Define("SIZE".to_string(), "8".to_string()))
+        );
+        assert_eq!(
+            Directive::parse("  # define FLAG"),
+            Some(Directive::Define("FLAG".to_string(), String::new()))
+        );
+        assert_eq!(
+            Directive::parse("#ifndef A"),
+            Some(Directive::IfNDef("A".to_string()))
+        );
+        assert_eq!(Directive::parse("#endif"), Some(Directive::EndIf));
+        assert_eq!(Directive::parse("#import core.wgsl"), None);
+        assert_eq!(Directive::parse("let a = 1;"), None);
+    }
+
+    #[test]
+    fn blocks_nest() {
+        let lines = [
+            "#ifdef A", "a", "#ifdef B", "ab", "#else", "a not b", "#endif", "#else", "not a",
+            "#endif", "always",
+        ];
+        let keep = |kept: &[usize]| -> Vec<bool> {
+            (0..lines.len()).map(|line| kept.contains(&line)).collect()
+        };
+        assert_eq!(kept(&lines, &defines(&["A", "B"])), Ok(keep(&[1, 3, 10])));
+        assert_eq!(kept(&lines, &defines(&["A"])), Ok(keep(&[1, 5, 10])));
+        // Neither branch of a block inside an inactive branch is kept
+        assert_eq!(kept(&lines, &defines(&["B"])), Ok(keep(&[8, 10])));
+        assert_eq!(kept(&lines, &defines(&[])), Ok(keep(&[8, 10])));
+
+        let lines = ["#ifndef A", "#ifndef B", "neither", "#endif", "#endif"];
+        assert!(kept(&lines, &defines(&[])).unwrap()[2]);
+        assert!(!kept(&lines, &defines(&["B"])).unwrap()[2]);
+    }
+
+    #[test]
+    fn unbalanced_blocks_fail() {
+        let err = |lines: &[&str]| kept(lines, &defines(&[])).unwrap_err();
+        assert_eq!(
+            err(&["#ifdef A", "#else", "#else", "#endif"]),
+            "#else appears twice in the same block"
+        );
+        assert_eq!(err(&["#else"]), "#else without #ifdef or #ifndef");
+        assert_eq!(
+            err(&["#ifdef A", "#endif", "#endif"]),
+            "#endif without #ifdef or #ifndef"
+        );
+        assert_eq!(err(&["#ifdef"]), "expected the name of a define");
+
+        let mut conditionals = Conditionals::default();
+        let (if_def, none) = (Directive::IfDef("A".to_string()), HashMap::new());
+        conditionals.apply(&if_def, 2, &none).unwrap();
+        conditionals.apply(&if_def, 5, &none).unwrap();
+        assert_eq!(
+            conditionals.finish(),
+            Err((5, "2 #ifdef or #ifndef without #endif".to_string()))
+        );
+    }
+
+    #[test]
+    fn placeholders_are_substituted() {
+        let defines = HashMap::from([
+            ("SIZE".to_string(), "8".to_string()),
+            ("NAME".to_string(), "life".to_string()),
+        ]);
+        assert_eq!(
+            substitute("let a: array<u32, #{SIZE}> = #{ NAME }(#{SIZE});", &defines),
+            Ok("let a: array<u32, 8> = life(8);".to_string())
+        );
+        assert_eq!(
+            substitute("let a = #{SIZE} + #{MISSING} + #{OTHER};", &defines),
+            Err("#{MISSING}".to_string())
+        );
+        assert_eq!(
+            substitute("let a = {SIZE};", &defines),
+            Ok("let a = {SIZE};".to_string())
+        );
+    }
+}
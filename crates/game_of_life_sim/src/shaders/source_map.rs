@@ -0,0 +1,220 @@
+use std::fmt;
+
+/// Where a line of a composed shader came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// A line of a shader file, counted from 1
+    File { path: String, line: usize },
+    /// A line of code generated into the shader, like the statement of a rule, counted from 1
+    /// within the generated code
+    Generated { name: String, line: usize },
+}
+
+/// A position in one of the files a shader was composed from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub origin: Origin,
+    /// The column on the line, counted from 1
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.origin {
+            Origin::File { path, line } => write!(f, "{path}:{line}:{}", self.column),
+            Origin::Generated { name, line } => write!(f, "{name}:{line}:{}", self.column),
+        }
+    }
+}
+
+/// The origin of every line of a composed shader
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    lines: Vec<Origin>,
+}
+
+impl SourceMap {
+    pub fn push(&mut self, origin: Origin) {
+        self.lines.push(origin);
+    }
+
+    /// The origin of a line of the composed shader, counted from 0
+    pub fn origin(&self, line: usize) -> Option<&Origin> {
+        self.lines.get(line)
+    }
+
+    /// The location in its original file of a byte offset into the composed `source`
+    pub fn locate(&self, source: &str, offset: usize) -> Option<Location> {
+        let prefix = source.get(..offset)?;
+        let line = prefix.matches('\n').count();
+        let line_start = prefix.rfind('\n').map_or(0, |newline| newline + 1);
+        Some(Location {
+            origin: self.origin(line)?.clone(),
+            column: prefix[line_start..].chars().count() + 1,
+        })
+    }
+}
+
+/// A shader put together from its file, the files it imports and generated code, with the
+/// origin of each of its lines
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComposedShader {
+    pub source: String,
+    pub source_map: SourceMap,
+    /// Every file the shader was composed from, in the order they were first read
+    pub files: Vec<String>,
+}
+
+impl ComposedShader {
+    pub fn push_line(&mut self, line: &str, origin: Origin) {
+        self.source.push_str(line);
+        self.source.push('\n');
+        self.source_map.push(origin);
+    }
+
+    pub fn append(&mut self, other: ComposedShader) {
+        self.source.push_str(&other.source);
+        self.source_map.lines.extend(other.source_map.lines);
+        self.files.extend(other.files);
+    }
+
+    /// Replace `placeholder` with generated `code` named `name`. A placeholder on a line of its
+    /// own is replaced by the lines of the code at the same indentation, which map to the
+    /// generated code. Elsewhere the line keeps its origin.
+    pub fn replace(&mut self, placeholder: &str, code: &str, name: &str) {
+        if !self.source.contains(placeholder) {
+            return;
+        }
+
+        let composed = std::mem::take(self);
+        self.files = composed.files;
+        for (line, origin) in composed.source.lines().zip(composed.source_map.lines) {
+            if !line.contains(placeholder) {
+                self.push_line(line, origin);
+            } else if line.trim() == placeholder {
+                let indent = &line[..line.len() - line.trim_start().len()];
+                for (index, code) in code.lines().enumerate() {
+                    let origin = Origin::Generated {
+                        name: name.to_string(),
+                        line: index + 1,
+                    };
+                    self.push_line(&format!("{indent}{code}"), origin);
+                }
+            } else {
+                let line = line.replace(placeholder, code);
+                let mut lines = line.lines();
+                self.push_line(lines.next().unwrap_or_default(), origin);
+                for (index, line) in lines.enumerate() {
+                    let origin = Origin::Generated {
+                        name: name.to_string(),
+                        line: index + 2,
+                    };
+                    self.push_line(line, origin);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, line: usize) -> Origin {
+        Origin::File {
+            path: path.to_string(),
+            line,
+        }
+    }
+
+    fn generated(line: usize) -> Origin {
+        Origin::Generated {
+            name: "rule".to_string(),
+            line,
+        }
+    }
+
+    fn composed(lines: &[&str]) -> ComposedShader {
+        let mut shader = ComposedShader::default();
+        for (index, line) in lines.iter().enumerate() {
+            shader.push_line(line, file("main.wgsl", index + 1));
+        }
+        shader
+    }
+
+    fn origins(shader: &ComposedShader) -> Vec<Origin> {
+        (0..shader.source.lines().count())
+            .map(|line| shader.source_map.origin(line).unwrap().clone())
+            .collect()
+    }
+
+    #[test]
+    fn placeholders_on_their_own_line_map_to_the_generated_code() {
+        let mut shader = composed(&["fn update() {", "    {STATEMENT}", "}"]);
+        shader.replace("{STATEMENT}", "let a = 1u;\nresult = a;", "rule");
+        assert_eq!(
+            shader.source,
+            "fn update() {\n    let a = 1u;\n    result = a;\n}\n"
+        );
+        assert_eq!(
+            origins(&shader),
+            [
+                file("main.wgsl", 1),
+                generated(1),
+                generated(2),
+                file("main.wgsl", 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn inline_placeholders_keep_the_origin_of_their_line() {
+        let mut shader = composed(&["let a = {VALUE};", "let b = 2u;"]);
+        shader.replace("{VALUE}", "1u", "rule");
+        assert_eq!(shader.source, "let a = 1u;\nlet b = 2u;\n");
+        assert_eq!(
+            origins(&shader),
+            [file("main.wgsl", 1), file("main.wgsl", 2)]
+        );
+
+        // The lines after the first line of the code are generated
+        let mut shader = composed(&["let a = max({VALUE});"]);
+        shader.replace("{VALUE}", "1u,\n2u", "rule");
+        assert_eq!(shader.source, "let a = max(1u,\n2u);\n");
+        assert_eq!(origins(&shader), [file("main.wgsl", 1), generated(2)]);
+
+        let unchanged = composed(&["let a = 1u;"]);
+        let mut shader = unchanged.clone();
+        shader.replace("{VALUE}", "2u", "rule");
+        assert_eq!(shader, unchanged);
+    }
+
+    #[test]
+    fn offsets_are_located_in_their_file() {
+        let mut shader = ComposedShader::default();
+        shader.push_line("fn core() {}", file("core.wgsl", 3));
+        shader.push_line("// π ≈ 3", file("core.wgsl", 4));
+        let mut main = ComposedShader::default();
+        main.push_line("fn main() {", file("main.wgsl", 2));
+        main.push_line("    core();", file("main.wgsl", 3));
+        shader.append(main);
+
+        let source = shader.source.clone();
+        let locate = |text: &str| {
+            let offset = source.find(text).unwrap();
+            shader
+                .source_map
+                .locate(&source, offset)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(locate("fn core"), "core.wgsl:3:1");
+        assert_eq!(locate("core() {}"), "core.wgsl:3:4");
+        // Columns count characters, not bytes
+        assert_eq!(locate("3\n"), "core.wgsl:4:8");
+        assert_eq!(locate("main"), "main.wgsl:2:4");
+        assert_eq!(locate("core();"), "main.wgsl:3:5");
+
+        assert_eq!(shader.source_map.locate(&source, source.len() + 1), None);
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    fmt,
+    io::{Read, Write},
+    path::Path,
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use crate::{cpu::Grid, dsl::Rule, shaders::ShaderError, Boundary};
+
+/// Snapshots of another version are refused instead of being restored wrong
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Everything needed to resume a session: the states of every cell, the generation, the rule and
+/// the edges it runs with and where the camera looks at. Saved as gzipped json.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// Generations stepped since the canvas was created
+    pub generation: u64,
+    pub rule: Rule,
+    /// The name of the rule in the catalog, if it was picked from there
+    pub catalog_name: Option<String>,
+    pub boundary: Boundary,
+    pub width: u32,
+    pub height: u32,
+    pub camera_position: [f32; 2],
+    pub camera_scale: f32,
+    /// The cells row by row as runs of (count, state)
+    cells: Vec<(u32, u32)>,
+}
+
+impl Snapshot {
+    /// A snapshot of the cells of `grid` under `rule` at generation 0, with the camera at the
+    /// center of the canvas
+    pub fn new(grid: &Grid, rule: Rule) -> Self {
+        let mut cells: Vec<(u32, u32)> = Vec::new();
+        for &state in grid.cells() {
+            match cells.last_mut() {
+                Some((count, last)) if *last == state => *count += 1,
+                _ => cells.push((1, state)),
+            }
+        }
+
+        Self {
+            version: SNAPSHOT_VERSION,
+            generation: 0,
+            rule,
+            catalog_name: None,
+            boundary: Boundary::default(),
+            width: grid.width(),
+            height: grid.height(),
+            camera_position: [0.0; 2],
+            camera_scale: 1.0,
+            cells,
+        }
+    }
+
+    /// The states of the cells, fails if the canvas is empty or too large or if the runs don't
+    /// cover it exactly
+    pub fn grid(&self) -> Result<Grid, SnapshotError> {
+        let size = format!("{}x{}", self.width, self.height);
+        if self.width == 0 || self.height == 0 || self.width.checked_mul(self.height).is_none() {
+            return Err(SnapshotError::Format(format!(
+                "a {size} canvas can't be restored"
+            )));
+        }
+
+        let len = self
+            .cells
+            .iter()
+            .map(|&(count, _)| count as u64)
+            .sum::<u64>();
+        if len != self.width as u64 * self.height as u64 {
+            return Err(SnapshotError::Format(format!(
+                "{len} cells don't fill a {size} canvas"
+            )));
+        }
+
+        let mut cells = Vec::with_capacity(len as usize);
+        for &(count, state) in &self.cells {
+            cells.resize(cells.len() + count as usize, state);
+        }
+        let grid = Grid::from_cells(self.width, self.height, cells)
+            .ok_or_else(|| SnapshotError::Format(format!("the cells don't fit a {size} canvas")))?;
+        Ok(grid.with_boundary(self.boundary))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SnapshotError> {
+        let json =
+            serde_json::to_vec(self).map_err(|err| SnapshotError::Format(err.to_string()))?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json)?;
+        Ok(encoder.finish()?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut json = Vec::new();
+        GzDecoder::new(bytes)
+            .read_to_end(&mut json)
+            .map_err(|err| SnapshotError::Format(err.to_string()))?;
+        let snapshot: Self =
+            serde_json::from_slice(&json).map_err(|err| SnapshotError::Format(err.to_string()))?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::Version(snapshot.version));
+        }
+        Ok(snapshot)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        Ok(std::fs::write(path, self.to_bytes()?)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+/// An error produced while saving, loading or restoring a snapshot
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    /// A file that isn't a snapshot or is damaged
+    Format(String),
+    /// A snapshot saved by another version
+    Version(u32),
+    /// A snapshot of a canvas of another size than this one
+    CanvasSize {
+        width: u32,
+        height: u32,
+    },
+    /// The canvas couldn't be read back from the GPU
+    Readback(glass::wgpu::BufferAsyncError),
+    /// The rule of the snapshot doesn't build
+    Shader(ShaderError),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "{err}"),
+            SnapshotError::Format(message) => write!(f, "not a valid snapshot: {message}"),
+            SnapshotError::Version(version) => write!(
+                f,
+                "the snapshot is of version {version}, only version {SNAPSHOT_VERSION} can be loaded"
+            ),
+            SnapshotError::CanvasSize { width, height } => write!(
+                f,
+                "the snapshot is of a {width}x{height} canvas, this one is {}x{}",
+                crate::SIM_SIZE,
+                crate::SIM_SIZE
+            ),
+            SnapshotError::Readback(err) => write!(f, "failed to read the canvas: {err}"),
+            SnapshotError::Shader(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(err: std::io::Error) -> Self {
+        SnapshotError::Io(err)
+    }
+}
+
+impl From<glass::wgpu::BufferAsyncError> for SnapshotError {
+    fn from(err: glass::wgpu::BufferAsyncError) -> Self {
+        SnapshotError::Readback(err)
+    }
+}
+
+impl From<ShaderError> for SnapshotError {
+    fn from(err: ShaderError) -> Self {
+        SnapshotError::Shader(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::rulesets;
+
+    fn snapshot() -> (Grid, Snapshot) {
+        let mut grid = Grid::new(7, 5).with_boundary(Boundary::Clamp);
+        for (x, y, state) in [(0, 0, 1), (1, 0, 1), (6, 2, 3), (3, 4, 2), (6, 4, 1)] {
+            grid.set(x, y, state);
+        }
+        let mut snapshot = Snapshot::new(&grid, rulesets::by_name("star_wars").unwrap());
+        snapshot.generation = 42;
+        snapshot.catalog_name = Some("star_wars".to_string());
+        snapshot.boundary = Boundary::Clamp;
+        snapshot.camera_position = [12.0, -3.5];
+        snapshot.camera_scale = 0.25;
+        (grid, snapshot)
+    }
+
+    #[test]
+    fn snapshots_round_trip() {
+        let (grid, snapshot) = snapshot();
+        let loaded = Snapshot::from_bytes(&snapshot.to_bytes().unwrap()).unwrap();
+        assert_eq!(loaded, snapshot);
+        assert_eq!(loaded.grid().unwrap(), grid);
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let (_, mut snapshot) = snapshot();
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        let err = Snapshot::from_bytes(&snapshot.to_bytes().unwrap()).unwrap_err();
+        assert!(matches!(err, SnapshotError::Version(version) if version == SNAPSHOT_VERSION + 1));
+    }
+
+    #[test]
+    fn damaged_snapshots_are_format_errors() {
+        let (_, snapshot) = snapshot();
+        assert!(matches!(
+            Snapshot::from_bytes(b"not gzip"),
+            Err(SnapshotError::Format(_))
+        ));
+
+        let sizes = [(0, 0), (0, 5), (7, 0), (u32::MAX, 2), (7, 4)];
+        for (width, height) in sizes {
+            let snapshot = Snapshot {
+                width,
+                height,
+                ..snapshot.clone()
+            };
+            assert!(
+                matches!(snapshot.grid(), Err(SnapshotError::Format(_))),
+                "{width}x{height}"
+            );
+        }
+    }
+}